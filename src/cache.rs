@@ -0,0 +1,122 @@
+use crate::{diffs, JournalDiff};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// bump whenever JournalDiff changes so files written by older versions aren't read
+const CACHE_VERSION: u32 = 2;
+
+// keeps temporary file names apart when several threads save at once
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// Diffs saved by commit id so they don't have to be made again on the next run.
+/// Like git's object store, each diff is a json file in a directory named by the
+/// first two characters of its id.
+#[derive(Debug, Clone)]
+pub struct DiffCache {
+    dir: PathBuf,
+}
+
+impl DiffCache {
+    /// Opens the cache in dir, emptying it if it was made by another version or with
+    /// other diff options
+    pub fn open(dir: &Path) -> Result<DiffCache, Box<dyn Error>> {
+        let stamp = format!("{}\n{}\n", CACHE_VERSION, diffs::diff_options());
+        let stamp_path = dir.join("version");
+        if fs::read_to_string(&stamp_path).ok().as_deref() != Some(stamp.as_str()) {
+            fs::create_dir_all(dir)?;
+            // only the two character directories so nothing else in dir is lost
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_name().len() == 2 && entry.file_type()?.is_dir() {
+                    fs::remove_dir_all(entry.path())?;
+                }
+            }
+            fs::write(&stamp_path, stamp)?;
+        }
+        Ok(DiffCache {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// The saved diff for a commit. Missing and unreadable files are both misses.
+    pub fn get(&self, id: &str) -> Option<JournalDiff> {
+        let text = fs::read_to_string(self.path(id)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Saves the diff for a commit. It's written to a temporary file and renamed so
+    /// a crash can't leave half a diff behind.
+    pub fn put(&self, id: &str, diff: &JournalDiff) -> Result<(), Box<dyn Error>> {
+        let path = self.path(id);
+        fs::create_dir_all(self.dir.join(shard(id)))?;
+        let temp = path.with_extension(format!(
+            "{}.{}.tmp",
+            process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, serde_json::to_string(diff)?)?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(shard(id)).join(format!("{}.json", id))
+    }
+}
+
+fn shard(id: &str) -> &str {
+    id.get(..2).unwrap_or("00")
+}
+
+pub fn get_cache_path() -> Result<String, Box<dyn Error>> {
+    let mut path = crate::config::get_base_path()?;
+    path.push_str("cache/");
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Expand;
+
+    #[test]
+    fn cache_test() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join("mmcj_cache_test");
+        let _ = fs::remove_dir_all(&dir);
+        let id = "aeb35fe55b5deabc36399617c9a7c9281226b67e";
+        let diff: JournalDiff = serde_json::from_value(serde_json::json!({
+            "commit": null,
+            "counts": { "added": 3, "deleted": 1, "modified": 0 },
+            "files": [],
+            "parents": [],
+        }))?;
+
+        let cache = DiffCache::open(&dir)?;
+        assert!(cache.get(id).is_none());
+        cache.put(id, &diff)?;
+        assert_eq!(Some(3), cache.get(id).and_then(|d| d.counts()).map(|c| c.added()));
+        assert!(dir.join("ae").join(format!("{}.json", id)).exists());
+
+        // opening again with the same version keeps what was saved
+        let cache = DiffCache::open(&dir)?;
+        assert!(cache.get(id).is_some());
+
+        fs::write(dir.join("notes.txt"), "mine")?;
+        fs::write(dir.join("version"), "0\nold options\n")?;
+        let cache = DiffCache::open(&dir)?;
+        assert!(cache.get(id).is_none(), "Diffs from another version should be thrown away");
+        assert!(dir.join("notes.txt").exists());
+
+        cache.put(id, &diff)?;
+        fs::write(cache.path(id), "not json")?;
+        assert!(cache.get(id).is_none());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
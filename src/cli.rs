@@ -0,0 +1,179 @@
+use chrono::NaiveDate;
+use std::error::Error;
+
+pub const USAGE: &str = "\
+Usage: mmcj [OPTIONS] [COMMAND]
+
+Commands:
+    tui                 Browse the journal interactively (default)
+    log                 Print one line per commit
+    stats               Print line counts for each repository
+    export              Print every diff as json
+    entry               Write a journal entry
+    tags                Print the tags used in entries
+    config              Print the settings being used
+    setup               Make new settings interactively
+
+Options:
+    -c, --config PATH   Read settings from PATH instead of ~/.gitintegratedjournal/settings.json
+    --since DATE        Only include days on or after DATE (YYYY-MM-DD)
+    --until DATE        Only include days on or before DATE (YYYY-MM-DD)
+    -r, --repo PATH     Use this repository instead of the ones in the settings, can be repeated
+    -e, --email EMAIL   Use this email instead of the ones in the settings, can be repeated
+    -t, --tag TAG       Only include entries with TAG and the commits they're about, can be repeated
+    -o, --output PATH   Write export to PATH instead of standard output
+    --no-cache          Diff every commit again instead of using saved diffs
+    -h, --help          Print this message
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Tui,
+    Log,
+    Stats,
+    Export,
+    Entry,
+    Tags,
+    Config,
+    Setup,
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub config: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    pub repos: Vec<String>,
+    pub emails: Vec<String>,
+    // lowercase without the #
+    pub tags: Vec<String>,
+    pub output: Option<String>,
+    pub no_cache: bool,
+}
+
+impl Args {
+    /// Parses the arguments after the program name. Options can come before or after
+    /// the command.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, Box<dyn Error>> {
+        let mut parsed = Args {
+            command: Command::Tui,
+            config: None,
+            since: None,
+            until: None,
+            repos: Vec::new(),
+            emails: Vec::new(),
+            tags: Vec::new(),
+            output: None,
+            no_cache: false,
+        };
+        let mut command = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || match args.next() {
+                Some(v) => Ok(v),
+                None => Err(crate::Error::new(&format!("{} needs a value", arg))),
+            };
+            match arg.as_str() {
+                "-c" | "--config" => parsed.config = Some(value()?),
+                "--since" => parsed.since = Some(parse_date(&value()?)?),
+                "--until" => parsed.until = Some(parse_date(&value()?)?),
+                "-r" | "--repo" => parsed.repos.push(value()?),
+                "-e" | "--email" => parsed.emails.push(value()?),
+                "-t" | "--tag" => parsed.tags.push(value()?.trim_start_matches('#').to_lowercase()),
+                "-o" | "--output" => parsed.output = Some(value()?),
+                "--no-cache" => parsed.no_cache = true,
+                "-h" | "--help" => parsed.command = Command::Help,
+                _ if arg.starts_with('-') => {
+                    return Err(Box::new(crate::Error::new(&format!("Unknown option {}", arg))));
+                }
+                _ if command.is_some() => {
+                    return Err(Box::new(crate::Error::new(&format!("Unexpected argument {}", arg))));
+                }
+                _ => command = Some(parse_command(&arg)?),
+            }
+        }
+
+        if parsed.command != Command::Help {
+            parsed.command = command.unwrap_or(Command::Tui);
+        }
+        Ok(parsed)
+    }
+
+    /// Whether a journal day falls inside --since and --until
+    pub fn in_range(&self, day: NaiveDate) -> bool {
+        self.since.is_none_or(|since| day >= since) && self.until.is_none_or(|until| day <= until)
+    }
+}
+
+fn parse_command(arg: &str) -> Result<Command, Box<dyn Error>> {
+    Ok(match arg {
+        "tui" => Command::Tui,
+        "log" => Command::Log,
+        "stats" => Command::Stats,
+        "export" => Command::Export,
+        "entry" => Command::Entry,
+        "tags" => Command::Tags,
+        "config" => Command::Config,
+        "setup" => Command::Setup,
+        "help" => Command::Help,
+        _ => return Err(Box::new(crate::Error::new(&format!("Unknown command {}", arg)))),
+    })
+}
+
+fn parse_date(arg: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    match NaiveDate::parse_from_str(arg, "%Y-%m-%d") {
+        Ok(date) => Ok(date),
+        Err(_) => Err(Box::new(crate::Error::new(&format!("Dates must be YYYY-MM-DD, not {}", arg)))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, Box<dyn Error>> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn parse_test() -> Result<(), Box<dyn Error>> {
+        let args = parse(&[])?;
+        assert_eq!(Command::Tui, args.command);
+        assert_eq!(None, args.config);
+
+        let args = parse(&["--since", "2019-12-01", "log", "-r", "a", "--repo", "b", "-e", "me@pm.com"])?;
+        assert_eq!(Command::Log, args.command);
+        assert_eq!(Some(NaiveDate::from_ymd(2019, 12, 1)), args.since);
+        assert_eq!(vec!["a", "b"], args.repos);
+        assert_eq!(vec!["me@pm.com"], args.emails);
+
+        assert!(args.tags.is_empty());
+        assert_eq!(vec!["debugging", "rust"], parse(&["tags", "-t", "#Debugging", "--tag", "rust"])?.tags);
+
+        assert!(!args.no_cache);
+        assert!(parse(&["--no-cache"])?.no_cache);
+
+        let args = parse(&["export", "--help"])?;
+        assert_eq!(Command::Help, args.command);
+
+        assert!(parse(&["--since", "yesterday"]).is_err());
+        assert!(parse(&["--config"]).is_err());
+        assert!(parse(&["log", "stats"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+        assert!(parse(&["frobnicate"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn in_range_test() -> Result<(), Box<dyn Error>> {
+        let args = parse(&["--since", "2019-12-01", "--until", "2019-12-31"])?;
+        assert!(args.in_range(NaiveDate::from_ymd(2019, 12, 1)));
+        assert!(args.in_range(NaiveDate::from_ymd(2019, 12, 31)));
+        assert!(!args.in_range(NaiveDate::from_ymd(2020, 1, 1)));
+        assert!(parse(&[])?.in_range(NaiveDate::from_ymd(1970, 1, 1)));
+        Ok(())
+    }
+}
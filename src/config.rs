@@ -1,3 +1,6 @@
+use crate::discovery::Discovery;
+use crate::glob::glob_match;
+use crate::identity::Identities;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::{error::Error, path::Path, collections::HashMap};
@@ -7,25 +10,35 @@ use termion::color;
 pub struct Config {
     pub indent_string: String,
     pub day_change_time: i32,
-    pub repos: Vec<String>,
+    #[serde(with = "repo_list")]
+    pub repos: Vec<RepoSettings>,
+    #[serde(default)]
+    pub discovery: Discovery,
     pub emails: Vec<String>,
+    // author names to match as well as emails, for commits made with a stray email
+    #[serde(default)]
+    pub names: Vec<String>,
+    // how much the lines of commits paired on with others count in stats
+    #[serde(default = "default_paired_weight")]
+    pub paired_weight: f64,
+    // how many repositories are scanned at once, 0 for one per processor
+    #[serde(default)]
+    pub scan_threads: usize,
     pub foreground_colors: HashMap<String, String>,
     pub background_colors: HashMap<String, String>,
 }
 
 impl Config {
     pub fn new() -> Result<Config, Box<dyn Error>> {
-        // command line argumtents don't do anything currently
-        //let args: Vec<String> = env::args().collect();
+        Config::from_path(&get_settings_path()?)
+    }
 
-        let mut path = get_base_path()?;
-        path.push_str("settings.json");
-        if !Path::new(&path).exists() {
-            //TODO: Throw error for now make setup dialouge latter
-            return Err(Box::new(super::Error::new("No Settings Found")));
+    pub fn from_path(path: &str) -> Result<Config, Box<dyn Error>> {
+        if !Path::new(path).exists() {
+            return Err(Box::new(super::Error::new(&format!("No Settings Found at {}", path))));
         }
 
-        let file_str = fs::read_to_string(&path)?;
+        let file_str = fs::read_to_string(path)?;
         Ok(serde_json::from_str(&file_str)?)
     }
 
@@ -35,6 +48,7 @@ impl Config {
             ("add", "green"),
             ("delete", "red"),
             ("modify", "blue"),
+            ("entry", "magenta"),
             ("highlight", "black"),
         ].iter().cloned().map(|t| (t.0.to_string(), t.1.to_string())).collect();
 
@@ -47,14 +61,35 @@ impl Config {
             indent_string: String::from("    "),
             day_change_time: 500,
             repos: Vec::new(),
+            discovery: Discovery::default(),
             emails: Vec::new(),
+            names: Vec::new(),
+            paired_weight: default_paired_weight(),
+            scan_threads: 0,
             foreground_colors,
             background_colors,
         }
     }
 
+    /// The emails and names whose commits go in the journal
+    pub fn identities(&self) -> Identities {
+        Identities::new(&self.emails, &self.names)
+    }
+
+    /// The listed repositories followed by the ones found under the discovery roots
+    pub fn all_repos(&self) -> Vec<RepoSettings> {
+        let mut repos = self.repos.clone();
+        for found in self.discovery.find_all() {
+            let found = found.to_string_lossy().into_owned();
+            if !repos.iter().any(|r| r.path == found) {
+                repos.push(RepoSettings::new(&found));
+            }
+        }
+        repos
+    }
+
     pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
-        let text = serde_json::to_string(&self)?;
+        let text = serde_json::to_string_pretty(&self)?;
         fs::write(path, text)?;
         Ok(())
     }
@@ -70,16 +105,175 @@ impl Config {
         for (setting, color_string) in &self.background_colors {
             colors.bg.insert(setting.clone(), get_color_escape(color_string, false)?);
         }
+        for repo in &self.repos {
+            if let Some(color_string) = &repo.color {
+                colors.fg.insert(repo_color_setting(&repo.name()), get_color_escape(color_string, true)?);
+            }
+        }
         Ok(colors)
     }
 }
 
+fn default_paired_weight() -> f64 {
+    1.0
+}
+
+/// Settings for one repository. In settings.json a repository can be just its path
+/// or an object with the path and any of the other settings.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RepoSettings {
+    pub path: String,
+    // name shown instead of the directory name
+    #[serde(default)]
+    pub alias: Option<String>,
+    // color the name is shown in
+    #[serde(default)]
+    pub color: Option<String>,
+    // emails only used for this repository, on top of the global ones
+    #[serde(default)]
+    pub emails: Vec<String>,
+    // globs of branch names to walk, every branch if empty. Remote branches are
+    // named with their remote like "origin/main".
+    #[serde(default)]
+    pub branches: Vec<String>,
+    // which kinds of refs commits are found from
+    #[serde(default)]
+    pub refs: RefSelection,
+    // globs of file paths left out of diffs
+    #[serde(default)]
+    pub exclude_paths: Vec<String>,
+}
+
+impl RepoSettings {
+    pub fn new(path: &str) -> RepoSettings {
+        RepoSettings {
+            path: path.to_string(),
+            alias: None,
+            color: None,
+            emails: Vec::new(),
+            branches: Vec::new(),
+            refs: RefSelection::default(),
+            exclude_paths: Vec::new(),
+        }
+    }
+
+    /// The alias, or the name of the directory the repository is in
+    pub fn name(&self) -> String {
+        if let Some(alias) = &self.alias {
+            return alias.clone();
+        }
+        let path = fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone().into());
+        match path.file_name() {
+            Some(n) => n.to_string_lossy().trim_end_matches(".git").to_string(),
+            None => self.path.clone(),
+        }
+    }
+
+    fn is_plain(&self) -> bool {
+        *self == RepoSettings::new(&self.path)
+    }
+}
+
+/// Which refs of a repository are walked for commits. Only local branches are by default.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RefSelection {
+    pub local: bool,
+    // remote tracking branches, for work pushed from another machine
+    pub remote: bool,
+    pub tags: bool,
+    // globs of full ref names like "refs/notes/*" to walk as well
+    pub patterns: Vec<String>,
+    // only walk from HEAD and ignore everything else
+    pub head_only: bool,
+}
+
+impl Default for RefSelection {
+    fn default() -> RefSelection {
+        RefSelection {
+            local: true,
+            remote: false,
+            tags: false,
+            patterns: Vec::new(),
+            head_only: false,
+        }
+    }
+}
+
+impl RefSelection {
+    /// Whether the full ref name is selected, with branches filtered by the globs
+    pub fn selects(&self, name: &str, branches: &[String]) -> bool {
+        let branch_matches =
+            |branch: &str| branches.is_empty() || branches.iter().any(|p| glob_match(p, branch));
+
+        if self.patterns.iter().any(|p| glob_match(p, name)) {
+            return true;
+        }
+        if let Some(branch) = name.strip_prefix("refs/heads/") {
+            return self.local && branch_matches(branch);
+        }
+        if let Some(branch) = name.strip_prefix("refs/remotes/") {
+            // origin/HEAD just points at another remote branch
+            return self.remote && !branch.ends_with("/HEAD") && branch_matches(branch);
+        }
+        name.starts_with("refs/tags/") && self.tags
+    }
+}
+
+/// The color setting a repository's name is drawn with
+pub fn repo_color_setting(name: &str) -> String {
+    format!("repo {}", name)
+}
+
+// Reads repositories written as plain paths or objects, and writes ones that only have
+// a path back as plain paths so older settings files stay the same.
+mod repo_list {
+    use super::RepoSettings;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize, Serialize)]
+    #[serde(untagged)]
+    enum RepoEntry {
+        Path(String),
+        Settings(RepoSettings),
+    }
+
+    pub fn serialize<S: Serializer>(repos: &[RepoSettings], serializer: S) -> Result<S::Ok, S::Error> {
+        let entries: Vec<RepoEntry> = repos
+            .iter()
+            .map(|r| {
+                if r.is_plain() {
+                    RepoEntry::Path(r.path.clone())
+                } else {
+                    RepoEntry::Settings(r.clone())
+                }
+            })
+            .collect();
+        entries.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<RepoSettings>, D::Error> {
+        let entries = Vec::<RepoEntry>::deserialize(deserializer)?;
+        Ok(entries
+            .into_iter()
+            .map(|e| match e {
+                RepoEntry::Path(path) => RepoSettings::new(&path),
+                RepoEntry::Settings(settings) => settings,
+            })
+            .collect())
+    }
+}
+
 pub struct Colors {
     fg: HashMap<String, String>,
     bg: HashMap<String, String>
 }
 
 impl Colors {
+    pub fn has_fg(&self, setting: &str) -> bool {
+        self.fg.contains_key(setting)
+    }
+
     pub fn fg(&self, setting: &str) -> &str {
         match self.fg.get(setting) {
             Some(s) => s, 
@@ -95,7 +289,7 @@ impl Colors {
     }
 }
 
-fn get_color_escape(str_color: &str, is_fg: bool) -> Result<String, Box<dyn Error>> {
+pub fn get_color_escape(str_color: &str, is_fg: bool) -> Result<String, Box<dyn Error>> {
     let mut found = true;
     let ansi;
     if is_fg {
@@ -171,3 +365,60 @@ fn get_color_escape(str_color: &str, is_fg: bool) -> Result<String, Box<dyn Erro
 pub fn get_base_path() -> Result<String, Box<dyn Error>> {
     Ok(format!("{}/.gitintegratedjournal/", std::env::var("HOME")?))
 }
+
+pub fn get_settings_path() -> Result<String, Box<dyn Error>> {
+    let mut path = get_base_path()?;
+    path.push_str("settings.json");
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repo_settings_test() -> Result<(), Box<dyn Error>> {
+        let text = r#"{
+            "indent_string": "    ",
+            "day_change_time": 500,
+            "repos": [
+                "mmcj_test_repo",
+                { "path": "../work", "alias": "Work", "color": "cyan", "branches": ["release/*"] }
+            ],
+            "emails": [],
+            "foreground_colors": {},
+            "background_colors": {}
+        }"#;
+        let config: Config = serde_json::from_str(text)?;
+        assert_eq!(RepoSettings::new("mmcj_test_repo"), config.repos[0]);
+        assert_eq!("mmcj_test_repo", config.repos[0].name());
+        assert_eq!("Work", config.repos[1].name());
+        assert_eq!(vec!["release/*"], config.repos[1].branches);
+        assert!(config.get_color_settings()?.has_fg("repo Work"));
+
+        let saved = serde_json::to_value(&config)?;
+        assert_eq!("mmcj_test_repo", saved["repos"][0]);
+        assert_eq!("Work", saved["repos"][1]["alias"]);
+        Ok(())
+    }
+
+    #[test]
+    fn ref_selection_test() {
+        let refs = RefSelection::default();
+        assert!(refs.selects("refs/heads/main", &[]));
+        assert!(!refs.selects("refs/remotes/origin/main", &[]));
+        assert!(!refs.selects("refs/heads/main", &[String::from("release/*")]));
+
+        let refs = RefSelection {
+            remote: true,
+            tags: true,
+            patterns: vec![String::from("refs/notes/*")],
+            ..RefSelection::default()
+        };
+        assert!(refs.selects("refs/remotes/origin/main", &[String::from("origin/*")]));
+        assert!(!refs.selects("refs/remotes/origin/HEAD", &[]));
+        assert!(refs.selects("refs/tags/v1.0", &[String::from("origin/*")]));
+        assert!(refs.selects("refs/notes/commits", &[]));
+        assert!(!refs.selects("refs/stash", &[]));
+    }
+}
@@ -1,32 +1,48 @@
-use crate::{folding_list::Expand, Colors};
-use git2::{Commit, Diff, Repository};
+use crate::{cache::DiffCache, config::repo_color_setting, folding_list::Expand, glob::glob_match, identity, Colors};
+use chrono::{DateTime, FixedOffset, TimeZone};
+use git2::{Commit, Delta, Diff, DiffFindOptions, Oid, Patch, Repository};
 use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, error::Error, fmt::Write, str};
-use termion::{clear, color};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    error::Error,
+    fmt::Write,
+    mem, str,
+    sync::{Arc, OnceLock},
+};
+use termion::{clear, color, style};
 
 #[derive(Debug, Clone)]
 struct DiffInfo {
     origin: char,
     content: String,
     file: String,
+    old_file: String,
+    status: Delta,
+    old_size: u64,
+    new_size: u64,
 }
 
 fn get_diff_info(info: &mut Vec<DiffInfo>, diff: Diff) -> Result<(), Box<dyn Error>> {
     let info_cell = RefCell::new(info);
 
     diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
-        let content = match str::from_utf8(line.content()) {
-            Err(_) => return false,
-            Ok(s) => s,
-        };
-
+        // text that isn't utf-8 (latin-1 files, odd file names) is decoded lossily
+        // instead of dropping the whole diff
         let mut entry = DiffInfo {
             origin: line.origin(),
-            content: content.to_string(),
+            content: String::from_utf8_lossy(line.content()).into_owned(),
             file: String::new(),
+            old_file: String::new(),
+            status: delta.status(),
+            old_size: delta.old_file().size(),
+            new_size: delta.new_file().size(),
         };
         if let Some(f) = delta.new_file().path_bytes() {
-            entry.file = str::from_utf8(f).unwrap().to_string();
+            entry.file = String::from_utf8_lossy(f).into_owned();
+        }
+        if let Some(f) = delta.old_file().path_bytes() {
+            entry.old_file = String::from_utf8_lossy(f).into_owned();
         }
         // git rid of trailing newline
         entry.content.pop();
@@ -37,38 +53,218 @@ fn get_diff_info(info: &mut Vec<DiffInfo>, diff: Diff) -> Result<(), Box<dyn Err
     Ok(())
 }
 
-// TODO: File rename appears as two seperate file changes, a deleted file and a made file
-// TODO: Initial Commit needs some stuff for it
-// TODO: Make way to make diff from Merge commit using seperate diffs for each merging branch
-// TODO: Make Modified Lines register as modified instead of
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JournalDiff {
+    commit: Option<CommitInfo>,
     counts: LineCounts,
     files: Vec<FileChanges>,
+    // for merge commits, the full diff against each parent
+    parents: Vec<ParentDiff>,
+    // whether the files are left out until the commit is expanded
+    #[serde(default)]
+    lazy: bool,
+    // globs of paths left out of a lazy diff once it's made
+    #[serde(default)]
+    exclude: Vec<String>,
+    // where the files of a lazy diff are read from and saved to
+    #[serde(skip)]
+    cache: Option<DiffCache>,
+    // the full diff of a lazy one once it's made, shared with its clones so expanding
+    // it again doesn't diff it again
+    #[serde(skip)]
+    loaded: Arc<OnceLock<JournalDiff>>,
 }
 
 impl JournalDiff {
     pub fn new() -> JournalDiff {
         JournalDiff {
+            commit: None,
             counts: LineCounts::new(),
             files: Vec::new(),
+            parents: Vec::new(),
+            lazy: false,
+            exclude: Vec::new(),
+            cache: None,
+            loaded: Arc::default(),
         }
     }
 
     pub fn from_commit(repo: &Repository, commit: &Commit) -> Result<JournalDiff, Box<dyn Error>> {
+        let mut journal = if commit.parent_count() > 1 {
+            JournalDiff::from_merge(repo, commit)?
+        } else {
+            JournalDiff::from_diff(parent_diff(repo, commit)?)?
+        };
+        journal.commit = Some(CommitInfo::new(repo, commit));
+        Ok(journal)
+    }
+
+    /// Only the commit information and added and deleted lines from git's diff stats,
+    /// leaving out paths matching the globs. Modified lines aren't counted until the
+    /// files are diffed when the commit is expanded. Merge commits are diffed right
+    /// away since their counts need every parent.
+    pub fn from_commit_lazy(
+        repo: &Repository,
+        commit: &Commit,
+        exclude: &[String],
+        cache: Option<&DiffCache>,
+    ) -> Result<JournalDiff, Box<dyn Error>> {
         if commit.parent_count() > 1 {
-            //TODO: Implement diff for a merge commit
-            return Err(Box::new(super::Error::new(
-                "Cannot git a diff for a merge commit yet",
-            )));
+            let mut full = JournalDiff::from_commit(repo, commit)?;
+            full.exclude_paths(exclude);
+            let journal = full.summary(exclude, cache);
+            let _ = journal.loaded.set(full);
+            return Ok(journal);
+        }
+
+        let mut diff = parent_diff(repo, commit)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true)))?;
+        let mut journal = JournalDiff::new();
+        journal.counts = stat_counts(&diff, exclude)?;
+        journal.commit = Some(CommitInfo::new(repo, commit));
+        journal.lazy = true;
+        journal.exclude = exclude.to_vec();
+        journal.cache = cache.cloned();
+        Ok(journal)
+    }
+
+    /// A copy with only the commit information and line counts, for keeping lots of
+    /// commits around without their files. exclude is what was left out of this diff
+    /// and the files are diffed again, or read from cache, when the commit is expanded.
+    /// The counts are this diff's so they match the files once they're made.
+    pub fn summary(&self, exclude: &[String], cache: Option<&DiffCache>) -> JournalDiff {
+        let mut journal = JournalDiff::new();
+        journal.commit = self.commit.clone();
+        journal.counts = self.counts.clone();
+        journal.lazy = true;
+        journal.exclude = exclude.to_vec();
+        journal.cache = cache.cloned();
+        journal
+    }
+
+    /// Makes the full diff of a lazy one from the cache, or by opening its repository
+    /// again and saving the result to the cache. It's only made the first time.
+    pub fn load(&self) -> Result<JournalDiff, Box<dyn Error>> {
+        let info = match &self.commit {
+            Some(c) => c,
+            None => return Err(Box::new(crate::Error::new("Diff has no commit to load"))),
+        };
+        let loaded = match self.loaded.get() {
+            Some(loaded) => loaded,
+            None => {
+                let mut journal = match self.cache.as_ref().and_then(|c| c.get(&info.id)) {
+                    Some(cached) => cached,
+                    None => {
+                        let repo = Repository::open(&info.repo_path)?;
+                        let commit = repo.find_commit(Oid::from_str(&info.id)?)?;
+                        let journal = JournalDiff::from_commit(&repo, &commit)?;
+                        if let Some(cache) = &self.cache {
+                            // a diff that can't be saved is only slower next time
+                            let _ = cache.put(&info.id, &journal);
+                        }
+                        journal
+                    }
+                };
+                journal.exclude_paths(&self.exclude);
+                self.loaded.get_or_init(|| journal)
+            }
+        };
+        let mut journal = loaded.clone();
+        // the summary has the repository name and pairing this run gave the commit
+        journal.commit = Some(info.clone());
+        Ok(journal)
+    }
+
+    pub fn is_lazy(&self) -> bool {
+        self.lazy
+    }
+
+    pub fn commit(&self) -> Option<&CommitInfo> {
+        self.commit.as_ref()
+    }
+
+    /// Points the commit at repo, for diffs made from another clone of it
+    pub fn set_repo(&mut self, repo: &Repository) {
+        if let Some(commit) = &mut self.commit {
+            let path = repo.workdir().unwrap_or_else(|| repo.path());
+            commit.repo = repo_name(repo);
+            commit.repo_path = path.to_string_lossy().into_owned();
+        }
+    }
+
+    /// Shows the commit as coming from name instead of the repository's directory
+    pub fn set_repo_name(&mut self, name: &str) {
+        if let Some(commit) = &mut self.commit {
+            commit.repo = name.to_string();
         }
+    }
+
+    /// Marks the commit as paired on with the named people
+    pub fn set_paired_with(&mut self, names: Vec<String>) {
+        if let Some(commit) = &mut self.commit {
+            commit.paired_with = names;
+        }
+    }
+
+    /// Removes the files whose path matches one of the globs and recounts the lines
+    pub fn exclude_paths(&mut self, patterns: &[String]) {
+        if patterns.is_empty() {
+            return;
+        }
+        // lazy diffs have no files yet, so the globs are kept for when they're made
+        if self.lazy {
+            self.exclude.extend(patterns.iter().cloned());
+            return;
+        }
+        let excluded = |file: &FileChanges| {
+            patterns
+                .iter()
+                .any(|p| glob_match(p, &file.path) || glob_match(p, &file.old_path))
+        };
+
+        self.files.retain(|f| !excluded(f));
+        self.counts = LineCounts::new();
+        for file in &self.files {
+            self.counts.add(&file.counts);
+        }
+
+        for parent in &mut self.parents {
+            parent.files.retain(|f| !excluded(f));
+            parent.counts = LineCounts::new();
+            for file in &parent.files {
+                parent.counts.add(&file.counts);
+            }
+        }
+    }
 
+    /// Diffs a merge commit against each of its parents. The top level files only keep
+    /// the hunks that add lines which are new relative to every parent. Those lines
+    /// could not have come from any of the merged branches, so they are the manual
+    /// conflict resolution.
+    fn from_merge(repo: &Repository, commit: &Commit) -> Result<JournalDiff, Box<dyn Error>> {
         let new_tree = repo.find_tree(commit.tree_id())?;
 
-        let old_commit = repo.find_commit(commit.parent_id(0)?)?;
-        let old_tree = repo.find_tree(old_commit.tree_id())?;
-        JournalDiff::from_diff(repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?)
+        let mut journal = JournalDiff::new();
+        for parent in commit.parents() {
+            let old_tree = repo.find_tree(parent.tree_id())?;
+            let diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+            journal.parents.push(ParentDiff::new(&parent, JournalDiff::from_diff(diff)?));
+        }
+
+        if let Some((first, rest)) = journal.parents.split_first() {
+            let mut resolved = Vec::new();
+            for file in &first.files {
+                if let Some(changes) = file.resolution(rest) {
+                    resolved.push(changes);
+                }
+            }
+            for changes in resolved {
+                journal.counts.add(&changes.counts);
+                journal.files.push(changes);
+            }
+        }
+        Ok(journal)
     }
 
     pub fn from_diff(diff: Diff) -> Result<JournalDiff, Box<dyn Error>> {
@@ -77,22 +273,282 @@ impl JournalDiff {
         Ok(journal)
     }
 
-    pub fn construct(&mut self, diff: Diff) -> Result<(), Box<dyn Error>> {
+    pub fn construct(&mut self, mut diff: Diff) -> Result<(), Box<dyn Error>> {
+        // pair up deleted and added files so renames and copies only count their real edits
+        diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true)))?;
+
         let mut info = Vec::new();
         get_diff_info(&mut info, diff)?;
-        let mut index = 0;
+        self.construct_files(&info);
+        Ok(())
+    }
 
-        while info.get(index).is_some() {
+    // builds a FileChanges for each file in the lines of a diff
+    fn construct_files(&mut self, info: &Vec<DiffInfo>) {
+        let mut index = 0;
+        while let Some(line) = info.get(index) {
+            // skip anything that doesn't belong to a file so the loop always moves forward
+            if line.origin != 'F' {
+                index += 1;
+                continue;
+            }
             let mut changes = FileChanges::new();
-            changes.construct(&info, &mut index);
+            changes.construct(info, &mut index);
             self.counts.add(&changes.counts);
             self.files.push(changes);
         }
-        Ok(())
     }
 }
 
 impl Expand for JournalDiff {
+    fn expand(&self) -> (Vec<Box<dyn Expand>>, bool) {
+        if self.lazy {
+            // a commit that can't be diffed anymore just has nothing to show
+            return match self.load() {
+                Ok(journal) => journal.expand(),
+                Err(_) => (Vec::new(), true),
+            };
+        }
+        let mut folds: Vec<Box<dyn Expand>> = Vec::new();
+        for file in &self.files {
+            folds.push(Box::new(file.clone()));
+        }
+        for parent in &self.parents {
+            folds.push(Box::new(parent.clone()));
+        }
+        (folds, true)
+    }
+
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
+    fn counts(&self) -> Option<LineCounts> {
+        // a lazy diff only knows its modified lines once it's been expanded
+        match self.loaded.get() {
+            Some(loaded) => Some(loaded.counts.clone()),
+            None => Some(self.counts.clone()),
+        }
+    }
+
+    fn is(&self, target: &str) -> bool {
+        self.commit.as_ref().is_some_and(|c| target == c.target())
+    }
+}
+
+impl JournalDiff {
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        let commit = match &self.commit {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        let time = commit.time().format("%Y-%m-%d %H:%M").to_string();
+        let paired = commit.paired_label();
+        let mut repo_setting = repo_color_setting(&commit.repo);
+        if !colors.is_some_and(|c| c.has_fg(&repo_setting)) {
+            repo_setting = String::from("default");
+        }
+        write_row(
+            stream,
+            colors,
+            width,
+            &[
+                ("modify", commit.short_id()),
+                ("default", " "),
+                (&repo_setting, &commit.repo),
+                ("default", " "),
+                ("default", &time),
+                ("default", " "),
+                ("default", commit.summary()),
+                ("default", &paired),
+            ],
+        )
+    }
+}
+
+// the diff of a commit against its first parent, or the empty tree for a root commit
+// so all of its files show as added
+fn parent_diff<'repo>(repo: &'repo Repository, commit: &Commit) -> Result<Diff<'repo>, Box<dyn Error>> {
+    let new_tree = repo.find_tree(commit.tree_id())?;
+    let old_tree = match commit.parent_count() {
+        0 => None,
+        _ => {
+            let old_commit = repo.find_commit(commit.parent_id(0)?)?;
+            Some(repo.find_tree(old_commit.tree_id())?)
+        }
+    };
+    Ok(repo.diff_tree_to_tree(old_tree.as_ref(), Some(&new_tree), None)?)
+}
+
+// added and deleted lines from git's stats, which can't tell modified lines apart.
+// Stats only cover the whole diff, so each file is counted on its own if some are excluded.
+fn stat_counts(diff: &Diff, exclude: &[String]) -> Result<LineCounts, Box<dyn Error>> {
+    let mut counts = LineCounts::new();
+    if exclude.is_empty() {
+        let stats = diff.stats()?;
+        counts.added = stats.insertions();
+        counts.deleted = stats.deletions();
+        return Ok(counts);
+    }
+
+    for (index, delta) in diff.deltas().enumerate() {
+        let excluded = [delta.old_file(), delta.new_file()].iter().any(|file| {
+            let path = file.path().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+            exclude.iter().any(|p| glob_match(p, &path))
+        });
+        if excluded {
+            continue;
+        }
+        if let Some(patch) = Patch::from_diff(diff, index)? {
+            let (_, added, deleted) = patch.line_stats()?;
+            counts.added += added;
+            counts.deleted += deleted;
+        }
+    }
+    Ok(counts)
+}
+
+/// Information about the commit a JournalDiff was made from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitInfo {
+    pub id: String,
+    pub repo: String,
+    pub repo_path: String,
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String,
+    pub parents: Vec<String>,
+    // everyone in the Co-authored-by trailers of the message
+    #[serde(default)]
+    pub co_authors: Vec<CoAuthor>,
+    // the other people on a commit that was paired on
+    #[serde(default)]
+    pub paired_with: Vec<String>,
+}
+
+impl CommitInfo {
+    pub fn new(repo: &Repository, commit: &Commit) -> CommitInfo {
+        let path = repo.workdir().unwrap_or_else(|| repo.path());
+        let message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+        CommitInfo {
+            id: commit.id().to_string(),
+            repo: repo_name(repo),
+            repo_path: path.to_string_lossy().into_owned(),
+            author: Signature::new(&commit.author()),
+            committer: Signature::new(&commit.committer()),
+            co_authors: identity::co_authors(&message)
+                .into_iter()
+                .map(|(name, email)| CoAuthor { name, email })
+                .collect(),
+            message,
+            parents: commit.parent_ids().map(|id| id.to_string()).collect(),
+            paired_with: Vec::new(),
+        }
+    }
+
+    /// When the work was done, which is the author time since rebasing changes the
+    /// committer time
+    pub fn time(&self) -> DateTime<FixedOffset> {
+        self.author.time
+    }
+
+    /// The first line of the message
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(7)]
+    }
+
+    /// " (paired with ...)" naming the other people on the commit, or nothing if there
+    /// weren't any
+    pub fn paired_label(&self) -> String {
+        if self.paired_with.is_empty() {
+            return String::new();
+        }
+        format!(" (paired with {})", self.paired_with.join(", "))
+    }
+
+    /// What FoldingList::reveal is given to find this commit
+    pub fn target(&self) -> String {
+        format!("commit {}", self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    // in the timezone the signature was made in
+    pub time: DateTime<FixedOffset>,
+}
+
+impl Signature {
+    pub fn new(signature: &git2::Signature) -> Signature {
+        let time = signature.when();
+        let offset = FixedOffset::east(time.offset_minutes() * 60);
+        Signature {
+            name: String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+            email: String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+            time: offset.timestamp(time.seconds(), 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+/// The name of the directory a repository is checked out in
+pub fn repo_name(repo: &Repository) -> String {
+    let path = repo.workdir().unwrap_or_else(|| repo.path());
+    let name = match path.file_name() {
+        Some(n) => n.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+    name.trim_end_matches(".git").to_string()
+}
+
+/// The diff of a merge commit against one of its parents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParentDiff {
+    parent: String,
+    counts: LineCounts,
+    files: Vec<FileChanges>,
+}
+
+impl ParentDiff {
+    fn new(parent: &Commit, diff: JournalDiff) -> ParentDiff {
+        ParentDiff {
+            parent: parent.id().to_string(),
+            counts: diff.counts,
+            files: diff.files,
+        }
+    }
+}
+
+impl ParentDiff {
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        write_row(
+            stream,
+            colors,
+            width,
+            &[
+                ("default", "merged from "),
+                ("modify", &self.parent[..self.parent.len().min(7)]),
+            ],
+        )
+    }
+}
+
+impl Expand for ParentDiff {
     fn expand(&self) -> (Vec<Box<dyn Expand>>, bool) {
         let mut folds: Vec<Box<dyn Expand>> = Vec::new();
         for file in &self.files {
@@ -101,16 +557,67 @@ impl Expand for JournalDiff {
         (folds, true)
     }
 
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
     fn counts(&self) -> Option<LineCounts> {
         Some(self.counts.clone())
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+    Other,
+}
+
+impl ChangeKind {
+    /// The letter and color setting git status style output would use
+    fn marker(self) -> (&'static str, &'static str) {
+        match self {
+            ChangeKind::Added => ("A", "add"),
+            ChangeKind::Deleted => ("D", "delete"),
+            ChangeKind::Modified => ("M", "modify"),
+            ChangeKind::Renamed => ("R", "modify"),
+            ChangeKind::Copied => ("C", "add"),
+            ChangeKind::Other => ("?", "default"),
+        }
+    }
+}
+
+impl From<Delta> for ChangeKind {
+    fn from(delta: Delta) -> ChangeKind {
+        match delta {
+            Delta::Added => ChangeKind::Added,
+            Delta::Deleted => ChangeKind::Deleted,
+            Delta::Modified => ChangeKind::Modified,
+            Delta::Renamed => ChangeKind::Renamed,
+            Delta::Copied => ChangeKind::Copied,
+            _ => ChangeKind::Other,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileChanges {
     counts: LineCounts,
     header: String,
     path: String,
+    old_path: String,
+    kind: ChangeKind,
+    // percent similarity for renames and copies
+    similarity: Option<u16>,
+    // old and new size in bytes of binary files
+    binary: Option<(u64, u64)>,
     hunks: Vec<Hunk>,
 }
 
@@ -120,10 +627,31 @@ impl FileChanges {
             counts: LineCounts::new(),
             header: String::new(),
             path: String::new(),
+            old_path: String::new(),
+            kind: ChangeKind::Modified,
+            similarity: None,
+            binary: None,
             hunks: Vec::new(),
         }
     }
 
+    /// Short description of changes that aren't lines, like "binary, 12 KB → 15 KB"
+    pub fn summary(&self) -> Option<String> {
+        self.binary
+            .map(|(old, new)| format!("binary, {} → {}", format_size(old), format_size(new)))
+    }
+
+    /// The path of the file, or "old → new (similarity%)" for renames and copies
+    pub fn name(&self) -> String {
+        match self.kind {
+            ChangeKind::Renamed | ChangeKind::Copied => match self.similarity {
+                Some(sim) => format!("{} → {} ({}%)", self.old_path, self.path, sim),
+                None => format!("{} → {}", self.old_path, self.path),
+            },
+            _ => self.path.clone(),
+        }
+    }
+
     fn construct(&mut self, info: &Vec<DiffInfo>, index: &mut usize) {
         if info[*index].origin != 'F' {
             return;
@@ -131,13 +659,114 @@ impl FileChanges {
 
         self.header.push_str(&info[*index].content);
         self.path.push_str(&info[*index].file);
+        self.old_path.push_str(&info[*index].old_file);
+        self.kind = ChangeKind::from(info[*index].status);
+        self.similarity = parse_similarity(&self.header);
         *index += 1;
-        while info.get(*index).is_some() && info[*index].origin == 'H' {
-            let hunk = Hunk::new(info, index);
-            self.counts.add(&hunk.counts);
-            self.hunks.push(hunk);
+        while let Some(line) = info.get(*index) {
+            match line.origin {
+                'H' => {
+                    let hunk = Hunk::new(info, index);
+                    self.counts.add(&hunk.counts);
+                    self.hunks.push(hunk);
+                }
+                'B' => {
+                    self.binary = Some((line.old_size, line.new_size));
+                    *index += 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn added_lines(&self) -> HashSet<&str> {
+        let mut added = HashSet::new();
+        for hunk in &self.hunks {
+            for line in &hunk.lines {
+                match line {
+                    DiffLine::Added(content) | DiffLine::Modified { new: content, .. } => {
+                        added.insert(content.as_str());
+                    }
+                    _ => (),
+                }
+            }
+        }
+        added
+    }
+
+    // Keeps the hunks with added lines that are also added relative to every other parent.
+    // Returns None if nothing in this file was resolved by hand.
+    fn resolution(&self, others: &[ParentDiff]) -> Option<FileChanges> {
+        let mut other_added = Vec::new();
+        for other in others {
+            let file = other.files.iter().find(|f| f.path == self.path)?;
+            other_added.push(file.added_lines());
+        }
+
+        let mut changes = FileChanges::new();
+        changes.header.push_str(&self.header);
+        changes.path.push_str(&self.path);
+        changes.old_path.push_str(&self.old_path);
+        changes.kind = self.kind;
+        changes.similarity = self.similarity;
+        changes.binary = self.binary;
+        for hunk in &self.hunks {
+            let is_resolution = hunk.lines.iter().any(|line| match line {
+                DiffLine::Added(content) | DiffLine::Modified { new: content, .. } => {
+                    other_added.iter().all(|added| added.contains(content.as_str()))
+                }
+                _ => false,
+            });
+            if is_resolution {
+                changes.counts.add(&hunk.counts);
+                changes.hunks.push(hunk.clone());
+            }
+        }
+
+        if changes.hunks.is_empty() {
+            None
+        } else {
+            Some(changes)
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    if bytes < KB {
+        format!("{} B", bytes)
+    } else if bytes < MB {
+        format!("{} KB", (bytes + KB / 2) / KB)
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    }
+}
+
+// reads the "similarity index 95%" line git writes in the header of renames and copies
+fn parse_similarity(header: &str) -> Option<u16> {
+    for line in header.lines() {
+        if let Some(rest) = line.strip_prefix("similarity index ") {
+            return rest.trim_end_matches('%').parse().ok();
         }
     }
+    None
+}
+
+impl FileChanges {
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        let (marker, setting) = self.kind.marker();
+        let summary = match self.summary() {
+            Some(s) => format!(" ({})", s),
+            None => String::new(),
+        };
+        write_row(
+            stream,
+            colors,
+            width,
+            &[(setting, marker), ("default", " "), ("default", &self.name()), ("default", &summary)],
+        )
+    }
 }
 
 impl Expand for FileChanges {
@@ -149,6 +778,14 @@ impl Expand for FileChanges {
         (folds, true)
     }
 
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
     fn counts(&self) -> Option<LineCounts> {
         Some(self.counts.clone())
     }
@@ -183,22 +820,26 @@ impl Hunk {
         }
 
         *index += 1;
+        let mut deleted = Vec::new();
+        let mut added = Vec::new();
         let mut origin = info[*index].origin;
         loop {
             match origin {
-                '+' => {
-                    hunk.counts.added += 1;
-                    hunk.lines
-                        .push(DiffLine::Added(info[*index].content.clone()));
-                }
+                '+' => added.push(info[*index].content.clone()),
                 '-' => {
-                    hunk.counts.deleted += 1;
+                    // a deletion after additions starts a new block of changes
+                    if !added.is_empty() {
+                        hunk.push_changes(&mut deleted, &mut added);
+                    }
+                    deleted.push(info[*index].content.clone());
+                }
+                ' ' => {
+                    hunk.push_changes(&mut deleted, &mut added);
                     hunk.lines
-                        .push(DiffLine::Deleted(info[*index].content.clone()));
+                        .push(DiffLine::Context(info[*index].content.clone()));
                 }
-                ' ' => hunk
-                    .lines
-                    .push(DiffLine::Context(info[*index].content.clone())),
+                // "\ No newline at end of file" markers
+                '=' | '>' | '<' => (),
                 _ => break,
             }
             *index += 1;
@@ -207,8 +848,90 @@ impl Hunk {
                 None => break,
             }
         }
+        hunk.push_changes(&mut deleted, &mut added);
         hunk
     }
+
+    // Pairs each deleted line with a similar added line that follows it and pushes the
+    // pairs as modified lines. Lines without a partner stay as deletions and additions.
+    fn push_changes(&mut self, deleted: &mut Vec<String>, added: &mut Vec<String>) {
+        // index of the first added line that hasn't been pushed yet
+        let mut next = 0;
+        for old in deleted.drain(..) {
+            let end = added.len().min(next + MODIFY_LOOKAHEAD + 1);
+            let partner = (next..end).find(|&i| similarity(&old, &added[i]) >= MODIFY_SIMILARITY);
+
+            match partner {
+                Some(pos) => {
+                    for line in &mut added[next..pos] {
+                        self.counts.added += 1;
+                        self.lines.push(DiffLine::Added(mem::take(line)));
+                    }
+                    self.counts.modified += 1;
+                    let new = mem::take(&mut added[pos]);
+                    self.lines.push(DiffLine::modified(old, new));
+                    next = pos + 1;
+                }
+                None => {
+                    self.counts.deleted += 1;
+                    self.lines.push(DiffLine::Deleted(old));
+                }
+            }
+        }
+        for line in added.drain(next..) {
+            self.counts.added += 1;
+            self.lines.push(DiffLine::Added(line));
+        }
+        added.clear();
+    }
+}
+
+// how many added lines to look past when searching for the partner of a deleted line
+const MODIFY_LOOKAHEAD: usize = 8;
+// how similar two lines have to be to count as one modified line
+const MODIFY_SIMILARITY: f64 = 0.5;
+// lines longer than this are never paired, comparing minified or generated lines
+// character by character would take too long
+const MODIFY_MAX_LENGTH: usize = 400;
+
+/// Everything that changes how a commit is diffed, so diffs saved with other options
+/// can be thrown away
+pub fn diff_options() -> String {
+    format!(
+        "renames copies lookahead={} similarity={} max_length={}",
+        MODIFY_LOOKAHEAD, MODIFY_SIMILARITY, MODIFY_MAX_LENGTH
+    )
+}
+
+/// Similarity of two lines from 0 to 1, based on the longest common subsequence of
+/// their non whitespace characters. Lines over MODIFY_MAX_LENGTH aren't similar to anything.
+fn similarity(old: &str, new: &str) -> f64 {
+    if old.len() > MODIFY_MAX_LENGTH || new.len() > MODIFY_MAX_LENGTH {
+        return 0.0;
+    }
+    let old: Vec<char> = old.chars().filter(|c| !c.is_whitespace()).collect();
+    let new: Vec<char> = new.chars().filter(|c| !c.is_whitespace()).collect();
+    if old.is_empty() && new.is_empty() {
+        return 1.0;
+    }
+    2.0 * lcs_len(&old, &new) as f64 / (old.len() + new.len()) as f64
+}
+
+fn lcs_len<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut row = vec![0; b.len() + 1];
+    for x in a {
+        let mut diag = 0;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if x == y {
+                diag + 1
+            } else {
+                above.max(row[j])
+            };
+            diag = above;
+        }
+    }
+    row[b.len()]
 }
 
 impl Expand for Hunk {
@@ -220,6 +943,14 @@ impl Expand for Hunk {
         (folds, false)
     }
 
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        write_row(stream, Some(colors), width, &[("modify", &self.header)])
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        write_row(stream, None, width, &[("modify", &self.header)])
+    }
+
     fn counts(&self) -> Option<LineCounts> {
         Some(self.counts.clone())
     }
@@ -230,10 +961,178 @@ pub enum DiffLine {
     Context(String),
     Added(String),
     Deleted(String),
-    //TODO: Add Moddified
+    // words holds the runs of changed words so they aren't worked out on every draw
+    Modified { old: String, new: String, words: Vec<(WordChange, String)> },
 }
 
-impl Expand for DiffLine {}
+impl DiffLine {
+    fn modified(old: String, new: String) -> DiffLine {
+        let words = word_diff(&old, &new);
+        DiffLine::Modified { old, new, words }
+    }
+
+    // draws the line without colors when colors is None so it can sit on the highlight
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        let fg = |setting| colors.map_or("", |c| c.fg(setting));
+        let mut room = width as usize;
+        match self {
+            DiffLine::Context(content) => {
+                write!(stream, "{}", fg("default"))?;
+                write_clipped(stream, " ", &mut room)?;
+                write_clipped(stream, content, &mut room)?;
+            }
+            DiffLine::Added(content) => {
+                write!(stream, "{}", fg("add"))?;
+                write_clipped(stream, "+", &mut room)?;
+                write_clipped(stream, content, &mut room)?;
+            }
+            DiffLine::Deleted(content) => {
+                write!(stream, "{}", fg("delete"))?;
+                write_clipped(stream, "-", &mut room)?;
+                write_clipped(stream, content, &mut room)?;
+            }
+            DiffLine::Modified { words, .. } => {
+                write!(stream, "{}", fg("modify"))?;
+                write_clipped(stream, "~", &mut room)?;
+                for (change, text) in words {
+                    match change {
+                        WordChange::Same => write!(stream, "{}", fg("modify"))?,
+                        WordChange::Deleted => write!(stream, "{}{}", fg("delete"), style::Underline)?,
+                        WordChange::Added => write!(stream, "{}{}", fg("add"), style::Underline)?,
+                    }
+                    write_clipped(stream, text, &mut room)?;
+                    write!(stream, "{}", style::NoUnderline)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Expand for DiffLine {
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+}
+
+/// Writes pieces of text with the foreground color setting they're paired with, clipped
+/// so there is a space left before the line counts. Colors are left out when colors is
+/// None so the row can be drawn on the highlight.
+pub fn write_row(
+    stream: &mut String,
+    colors: Option<&Colors>,
+    width: u16,
+    pieces: &[(&str, &str)],
+) -> Result<(), Box<dyn Error>> {
+    let mut room = width.saturating_sub(1) as usize;
+    for (setting, text) in pieces {
+        if let Some(c) = colors {
+            write!(stream, "{}", c.fg(setting))?;
+        }
+        write_clipped(stream, text, &mut room)?;
+    }
+    Ok(())
+}
+
+// writes as much of text as fits in the room left on the line
+fn write_clipped(stream: &mut String, text: &str, room: &mut usize) -> Result<(), Box<dyn Error>> {
+    for ch in text.chars() {
+        // tabs are expanded so the width of the line is known
+        let (ch, len) = if ch == '\t' { (' ', 4) } else { (ch, 1) };
+        for _ in 0..len {
+            if *room == 0 {
+                return Ok(());
+            }
+            stream.push(ch);
+            *room -= 1;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WordChange {
+    Same,
+    Deleted,
+    Added,
+}
+
+/// Splits a modified line into runs of words that stayed the same, were deleted and were
+/// added, in the order they should be drawn. Deleted words come before the words that
+/// replaced them. Lines over MODIFY_MAX_LENGTH are shown as wholly replaced.
+fn word_diff(old: &str, new: &str) -> Vec<(WordChange, String)> {
+    if old.len() > MODIFY_MAX_LENGTH || new.len() > MODIFY_MAX_LENGTH {
+        return vec![(WordChange::Deleted, old.to_string()), (WordChange::Added, new.to_string())];
+    }
+    let old = tokenize(old);
+    let new = tokenize(new);
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut segments: Vec<(WordChange, String)> = Vec::new();
+    let mut push = |change, token: &str| match segments.last_mut() {
+        Some((last, text)) if *last == change => text.push_str(token),
+        _ => segments.push((change, token.to_string())),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            push(WordChange::Same, old[i]);
+            i += 1;
+            j += 1;
+        } else if j >= new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(WordChange::Deleted, old[i]);
+            i += 1;
+        } else {
+            push(WordChange::Added, new[j]);
+            j += 1;
+        }
+    }
+    segments
+}
+
+// splits a line into words, runs of whitespace and single punctuation characters
+fn tokenize(line: &str) -> Vec<&str> {
+    let class = |ch: char| {
+        if ch.is_alphanumeric() || ch == '_' {
+            0
+        } else if ch.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut last = None;
+    for (pos, ch) in line.char_indices() {
+        let current = class(ch);
+        if pos > start && (last != Some(current) || current == 2) {
+            tokens.push(&line[start..pos]);
+            start = pos;
+        }
+        last = Some(current);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LineCounts {
@@ -251,12 +1150,34 @@ impl LineCounts {
         }
     }
 
-    fn add(&mut self, rhs: &LineCounts) {
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    pub fn deleted(&self) -> usize {
+        self.deleted
+    }
+
+    pub fn modified(&self) -> usize {
+        self.modified
+    }
+
+    pub fn add(&mut self, rhs: &LineCounts) {
         self.added += rhs.added;
         self.deleted += rhs.deleted;
         self.modified += rhs.modified;
     }
 
+    /// The counts multiplied by weight, rounded to whole lines
+    pub fn scaled(&self, weight: f64) -> LineCounts {
+        let scale = |n: usize| (n as f64 * weight).round().max(0.0) as usize;
+        LineCounts {
+            added: scale(self.added),
+            deleted: scale(self.deleted),
+            modified: scale(self.modified),
+        }
+    }
+
     pub fn char_width(&self) -> u16 {
         let string = format!("{} {} {}", self.added, self.deleted, self.modified);
         string.len() as u16
@@ -293,7 +1214,7 @@ impl LineCounts {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use git2::Oid;
+    use crate::testing::{commit_at, test_repo};
 
     fn get_diff_from_commit<'repo>(
         repo: &'repo Repository,
@@ -330,4 +1251,327 @@ mod tests {
 
         Ok(())
     }
+
+    fn test_info(lines: &[(char, &str)]) -> Vec<DiffInfo> {
+        let mut info = vec![DiffInfo {
+            origin: 'H',
+            content: String::from("@@ -1,4 +1,4 @@ fn main() {"),
+            file: String::new(),
+            old_file: String::new(),
+            status: Delta::Modified,
+            old_size: 0,
+            new_size: 0,
+        }];
+        for (origin, content) in lines {
+            let mut entry = info[0].clone();
+            entry.origin = *origin;
+            entry.content = content.to_string();
+            info.push(entry);
+        }
+        info
+    }
+
+    #[test]
+    fn hunk_modified_test() {
+        let info = test_info(&[
+            (' ', "fn main() {"),
+            ('-', "    let count = 10;"),
+            ('-', "    println!(\"{}\", count);"),
+            ('+', "    // a brand new comment"),
+            ('+', "    let total = 10;"),
+            ('+', "    println!(\"{}\", total);"),
+            (' ', "}"),
+        ]);
+        let mut index = 0;
+        let hunk = Hunk::new(&info, &mut index);
+        assert_eq!(info.len(), index);
+        assert_eq!("@@ -1,4 +1,4 @@", hunk.header);
+
+        assert_eq!(1, hunk.counts.added);
+        assert_eq!(0, hunk.counts.deleted);
+        assert_eq!(2, hunk.counts.modified);
+        assert_eq!(5, hunk.lines.len());
+        match &hunk.lines[2] {
+            DiffLine::Modified { old, new, .. } => {
+                assert_eq!("    let count = 10;", old);
+                assert_eq!("    let total = 10;", new);
+            }
+            line => panic!("Expected modified line, got {:?}", line),
+        }
+
+        let info = test_info(&[('-', "use std::fs;"), ('+', "mod config;")]);
+        let mut index = 0;
+        let hunk = Hunk::new(&info, &mut index);
+        assert_eq!(1, hunk.counts.added);
+        assert_eq!(1, hunk.counts.deleted);
+        assert_eq!(0, hunk.counts.modified);
+
+        // minified lines are too long to compare, even when they're almost the same
+        let old = "var a=1;".repeat(1000);
+        let new = format!("{}var b=2;", old);
+        let info = test_info(&[('-', &old), ('+', &new)]);
+        let mut index = 0;
+        let hunk = Hunk::new(&info, &mut index);
+        assert_eq!(1, hunk.counts.added);
+        assert_eq!(1, hunk.counts.deleted);
+        assert_eq!(0, hunk.counts.modified);
+    }
+
+    #[test]
+    fn word_diff_test() {
+        assert_eq!(vec!["let", " ", "count", " ", "=", " ", "10", ";"], tokenize("let count = 10;"));
+
+        let segments = word_diff("let count = 10;", "let total = 10;");
+        assert_eq!(
+            vec![
+                (WordChange::Same, String::from("let ")),
+                (WordChange::Deleted, String::from("count")),
+                (WordChange::Added, String::from("total")),
+                (WordChange::Same, String::from(" = 10;")),
+            ],
+            segments
+        );
+
+        let segments = word_diff("f(a)", "f(a, b)");
+        assert_eq!(
+            vec![
+                (WordChange::Same, String::from("f(a")),
+                (WordChange::Added, String::from(", b")),
+                (WordChange::Same, String::from(")")),
+            ],
+            segments
+        );
+
+        let long = "x".repeat(MODIFY_MAX_LENGTH + 1);
+        assert_eq!(
+            vec![(WordChange::Deleted, long.clone()), (WordChange::Added, String::from("y"))],
+            word_diff(&long, "y")
+        );
+    }
+
+    #[test]
+    fn render_clipped_test() -> Result<(), Box<dyn Error>> {
+        let mut stream = String::new();
+        let line = DiffLine::modified(String::from("let count = 10;"), String::from("let total = 10;"));
+        line.render(&mut stream, None, 8)?;
+        let plain = stream
+            .replace(&style::Underline.to_string(), "")
+            .replace(&style::NoUnderline.to_string(), "");
+        assert_eq!("~let cou", plain);
+
+        let mut stream = String::new();
+        DiffLine::Added(String::from("\tx")).render(&mut stream, None, 4)?;
+        assert_eq!("+   ", stream);
+        Ok(())
+    }
+
+    #[test]
+    fn binary_summary_test() {
+        let mut info = test_info(&[]);
+        info[0].origin = 'F';
+        info[0].file = String::from("logo.png");
+        info.push(DiffInfo {
+            origin: 'B',
+            content: String::from("Binary files a/logo.png and b/logo.png differ"),
+            old_size: 12 * 1024,
+            new_size: 15 * 1024 + 100,
+            ..info[0].clone()
+        });
+        // stray lines before a file shouldn't stop construction
+        info.insert(0, DiffInfo { origin: '>', ..info[0].clone() });
+        info.insert(0, DiffInfo { origin: 'H', ..info[0].clone() });
+
+        let mut journal = JournalDiff::new();
+        journal.construct_files(&info);
+        assert_eq!(1, journal.files.len());
+        assert_eq!("logo.png", journal.files[0].path);
+        assert_eq!(Some(String::from("binary, 12 KB → 15 KB")), journal.files[0].summary());
+
+        assert_eq!("900 B", format_size(900));
+        assert_eq!("1.5 MB", format_size(1024 * 1536));
+    }
+
+    #[test]
+    fn render_rows_test() -> Result<(), Box<dyn Error>> {
+        let mut journal = JournalDiff::new();
+        let signature = Signature {
+            name: String::from("Celnardur"),
+            email: String::from("celnardur@pm.com"),
+            time: FixedOffset::east(3600).ymd(2019, 12, 20).and_hms(14, 3, 0),
+        };
+        journal.commit = Some(CommitInfo {
+            id: String::from("aeb35fe55b5deabc36399617c9a7c9281226b67e"),
+            repo: String::from("mmcj"),
+            repo_path: String::from("/home/celnardur/mmcj"),
+            author: signature.clone(),
+            committer: signature,
+            message: String::from("Add folding list\n\nLonger description"),
+            parents: Vec::new(),
+            co_authors: Vec::new(),
+            paired_with: Vec::new(),
+        });
+        let mut stream = String::new();
+        journal.render(&mut stream, None, 80)?;
+        assert_eq!("aeb35fe mmcj 2019-12-20 14:03 Add folding list", stream);
+
+        let mut stream = String::new();
+        journal.render(&mut stream, None, 13)?;
+        assert_eq!("aeb35fe mmcj", stream);
+
+        journal.set_paired_with(vec![String::from("Someone"), String::from("Aaron")]);
+        let mut stream = String::new();
+        journal.render(&mut stream, None, 80)?;
+        assert_eq!("aeb35fe mmcj 2019-12-20 14:03 Add folding list (paired with Someone, Aaron)", stream);
+
+        let mut file = FileChanges::new();
+        file.path.push_str("src/main.rs");
+        file.kind = ChangeKind::Added;
+        let mut stream = String::new();
+        file.render(&mut stream, None, 80)?;
+        assert_eq!("A src/main.rs", stream);
+        Ok(())
+    }
+
+    #[test]
+    fn from_commit_lazy_test() -> Result<(), Box<dyn Error>> {
+        let (dir, repo) = test_repo("mmcj_lazy_test/repo")?;
+        let mut oid = None;
+        for text in ["fn main() {\n    println!(\"hi\");\n}\n", "fn main() {\n    println!(\"hello\");\n}\n"] {
+            oid = Some(commit_at(&repo, 1_576_800_000, "change", &[("main.rs", text), ("Cargo.lock", text)])?);
+        }
+        let commit = repo.find_commit(oid.unwrap())?;
+        let cache_dir = std::env::temp_dir().join("mmcj_lazy_test/cache");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let cache = DiffCache::open(&cache_dir)?;
+
+        let exclude = [String::from("*.lock")];
+        let mut full = JournalDiff::from_commit(&repo, &commit)?;
+        full.exclude_paths(&exclude);
+        let summary = full.summary(&exclude, Some(&cache));
+        assert!(summary.is_lazy());
+        assert!(summary.files.is_empty());
+        assert_eq!(1, summary.counts.modified, "Summaries should pair lines like full diffs");
+
+        // stats can't pair lines, so a lazy diff counts them once it's expanded
+        let lazy = JournalDiff::from_commit_lazy(&repo, &commit, &exclude, Some(&cache))?;
+        assert!(lazy.files.is_empty());
+        assert_eq!(Some((1, 1, 0)), lazy.counts().map(|c| (c.added, c.deleted, c.modified)));
+        let (folds, _) = lazy.expand();
+        assert_eq!(1, folds.len(), "Expanding should diff the files that aren't excluded");
+        assert_eq!(Some(1), folds[0].counts().map(|c| c.modified));
+        assert_eq!(Some((0, 0, 1)), lazy.counts().map(|c| (c.added, c.deleted, c.modified)));
+        assert!(cache.get(&commit.id().to_string()).is_some(), "Expanding should save the diff");
+
+        // without a cache, a clone of the row expands from the diff made the first time
+        let uncached = JournalDiff::from_commit_lazy(&repo, &commit, &exclude, None)?;
+        uncached.expand();
+        let row = uncached.clone();
+
+        // once it's made the repository isn't needed to expand it again
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(1, summary.load()?.files.len());
+        assert_eq!(1, row.load()?.files.len());
+
+        std::fs::remove_dir_all(&cache_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn exclude_paths_test() {
+        let mut journal = JournalDiff::new();
+        for file in [test_file("src/lib.rs", &["a"]), test_file("Cargo.lock", &["b", "c"])] {
+            journal.counts.add(&file.counts);
+            journal.files.push(file);
+        }
+        journal.exclude_paths(&[String::from("*.lock")]);
+        assert_eq!(1, journal.files.len());
+        assert_eq!(1, journal.counts.added);
+    }
+
+    #[test]
+    fn scaled_test() {
+        let counts = LineCounts {
+            added: 10,
+            deleted: 3,
+            modified: 0,
+        };
+        let half = counts.scaled(0.5);
+        assert_eq!((5, 2, 0), (half.added(), half.deleted(), half.modified()));
+        assert_eq!(0, counts.scaled(-1.0).added());
+    }
+
+    #[test]
+    fn rename_name_test() {
+        let header = "diff --git a/a.rs b/b.rs\nsimilarity index 95%\nrename from a.rs\nrename to b.rs";
+        assert_eq!(Some(95), parse_similarity(header));
+        assert_eq!(None, parse_similarity("diff --git a/a.rs b/a.rs"));
+
+        let mut file = FileChanges::new();
+        file.path.push_str("b.rs");
+        file.old_path.push_str("a.rs");
+        assert_eq!("b.rs", file.name());
+
+        file.kind = ChangeKind::Renamed;
+        file.similarity = parse_similarity(header);
+        assert_eq!("a.rs → b.rs (95%)", file.name());
+    }
+
+    #[test]
+    fn from_commit_test() -> Result<(), Box<dyn Error>> {
+        let repo = Repository::open("mmcj_test_repo")?;
+        let walk = crate::get_repo_revwalk(&repo)?;
+
+        let mut roots = 0;
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            let diff = JournalDiff::from_commit(&repo, &commit)?;
+            let info = diff.commit().expect("Diffs from commits should have commit info");
+            assert_eq!(commit.id().to_string(), info.id);
+            assert_eq!("mmcj_test_repo", info.repo);
+            assert_eq!(commit.parent_count(), info.parents.len());
+            if commit.parent_count() == 0 {
+                roots += 1;
+                assert!(!diff.files.is_empty(), "Root commit should add files");
+                assert!(diff.commit().unwrap().parents.is_empty());
+                assert_eq!(0, diff.counts.deleted, "Root commit cannot delete lines");
+            }
+        }
+        assert!(roots > 0, "Test repo should have a root commit");
+        Ok(())
+    }
+
+    fn test_file(path: &str, added: &[&str]) -> FileChanges {
+        let mut hunk = Hunk {
+            counts: LineCounts::new(),
+            header: String::from("@@ -1 +1 @@"),
+            lines: Vec::new(),
+        };
+        for line in added {
+            hunk.counts.added += 1;
+            hunk.lines.push(DiffLine::Added(line.to_string()));
+        }
+        let mut file = FileChanges::new();
+        file.path.push_str(path);
+        file.counts.add(&hunk.counts);
+        file.hunks.push(hunk);
+        file
+    }
+
+    #[test]
+    fn resolution_test() {
+        let theirs = ParentDiff {
+            parent: String::new(),
+            counts: LineCounts::new(),
+            files: vec![test_file("a.txt", &["ours", "resolved"]), test_file("b.txt", &["b"])],
+        };
+
+        let resolved = test_file("a.txt", &["theirs", "resolved"]).resolution(&[theirs.clone()]);
+        let resolved = resolved.expect("line added relative to both parents should be kept");
+        assert_eq!(1, resolved.hunks.len());
+        assert_eq!(2, resolved.counts.added);
+
+        // only changed relative to one parent, so it was taken from a branch as is
+        assert!(test_file("a.txt", &["theirs"]).resolution(&[theirs.clone()]).is_none());
+        assert!(test_file("c.txt", &["c"]).resolution(&[theirs]).is_none());
+    }
 }
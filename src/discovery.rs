@@ -0,0 +1,149 @@
+use crate::glob::glob_match;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Settings for finding repositories under root directories instead of listing each one.
+/// Globs are matched against the path of a directory relative to its root, like
+/// "work/*" or "**/node_modules".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Discovery {
+    pub roots: Vec<String>,
+    // if not empty, only repositories matching one of these are used
+    pub include: Vec<String>,
+    // directories matching one of these aren't searched at all
+    pub exclude: Vec<String>,
+    pub max_depth: usize,
+}
+
+impl Default for Discovery {
+    fn default() -> Discovery {
+        Discovery {
+            roots: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_depth: 4,
+        }
+    }
+}
+
+impl Discovery {
+    /// Finds the repositories under every root, sorted by path
+    pub fn find_all(&self) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for root in &self.roots {
+            let root = expand_home(root);
+            self.walk(&root, &root, 0, &mut found);
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// Finds the repositories under root. Repositories nested inside other repositories
+    /// and worktrees, which have a .git file instead of a directory, are included.
+    pub fn find(&self, root: &Path) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        self.walk(root, root, 0, &mut found);
+        found
+    }
+
+    fn walk(&self, root: &Path, dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+        let relative = relative_path(root, dir);
+        if !relative.is_empty() && self.exclude.iter().any(|p| glob_match(p, &relative)) {
+            return;
+        }
+
+        let included = self.include.is_empty() || self.include.iter().any(|p| glob_match(p, &relative));
+        if included && dir.join(".git").exists() {
+            found.push(dir.to_path_buf());
+        }
+        if depth >= self.max_depth {
+            return;
+        }
+
+        let entries = match fs::read_dir(dir) {
+            Ok(e) => e,
+            Err(_) => return,
+        };
+        // symlinks aren't followed so links back up the tree can't loop forever
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .map(|e| e.path())
+            .collect();
+        dirs.sort();
+        for sub in dirs {
+            self.walk(root, &sub, depth + 1, found);
+        }
+    }
+}
+
+/// Replaces a leading ~ with the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    if path == "~" || path.starts_with("~/") {
+        if let Ok(home) = std::env::var("HOME") {
+            return Path::new(&home).join(path.trim_start_matches('~').trim_start_matches('/'));
+        }
+    }
+    PathBuf::from(path)
+}
+
+// the path of dir under root with / between directories
+fn relative_path(root: &Path, dir: &Path) -> String {
+    let relative = dir.strip_prefix(root).unwrap_or(dir);
+    let parts: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Repository;
+
+    #[test]
+    fn find_test() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("mmcj_discovery_test");
+        let _ = fs::remove_dir_all(&dir);
+        Repository::init(dir.join("work/mmcj"))?;
+        Repository::init(dir.join("work/mmcj/vendor/lib"))?;
+        Repository::init(dir.join("work/web/node_modules/dep"))?;
+        Repository::init(dir.join("play/deep/down/game"))?;
+        fs::create_dir_all(dir.join("work/mmcj-feature"))?;
+        fs::write(dir.join("work/mmcj-feature/.git"), "gitdir: ../mmcj/.git/worktrees/feature")?;
+
+        let mut discovery = Discovery::default();
+        discovery.roots.push(dir.to_string_lossy().into_owned());
+        discovery.exclude.push(String::from("**/node_modules"));
+        assert_eq!(
+            vec![
+                dir.join("play/deep/down/game"),
+                dir.join("work/mmcj"),
+                dir.join("work/mmcj/vendor/lib"),
+                dir.join("work/mmcj-feature"),
+            ],
+            discovery.find_all()
+        );
+
+        discovery.max_depth = 2;
+        discovery.include.push(String::from("work/*"));
+        assert_eq!(vec![dir.join("work/mmcj"), dir.join("work/mmcj-feature")], discovery.find_all());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn expand_home_test() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(Path::new(&home).join("code"), expand_home("~/code"));
+        assert_eq!(PathBuf::from("/code/~"), expand_home("/code/~"));
+    }
+}
@@ -1,9 +1,886 @@
-use chrono::NaiveDateTime;
+use crate::{diffs::write_row, tags, CommitInfo, Colors, Expand};
+use chrono::{Local, NaiveDateTime};
+use git2::Repository;
+use std::{
+    collections::HashSet,
+    env,
+    error::Error,
+    fs,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, OnceLock},
+};
 
-// journal entry that you wrote yourself
-#[allow(dead_code)]
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+// longest the lines of an expanded entry get, even on wide terminals
+const WRAP_WIDTH: usize = 80;
+
+/// A journal entry that you wrote yourself. Each one is saved as a markdown file named
+/// by its id, with the id and time in front matter and the title as a heading:
+///
+/// ```text
+/// ---
+/// id: 20191220-140300-3f2a
+/// time: 2019-12-20 14:03:00
+/// ref: /home/me/code/mmcj | 3f2a9c1e... | src/folding_list.rs:120-180
+/// ---
+/// # Fixed the folding list
+///
+/// It was the segments.
+/// ```
+#[derive(Debug, Clone, PartialEq)]
 pub struct Entry {
-    time: NaiveDateTime,
-    title: Option<String>,
-    text: String,
+    // never changes once made, even if the time is edited
+    pub id: String,
+    pub time: NaiveDateTime,
+    pub title: Option<String>,
+    pub text: String,
+    pub references: Vec<Reference>,
+    // ref: lines that couldn't be read, kept as written so saving doesn't lose them
+    pub broken_references: Vec<String>,
+    // the references once they're validated, which happens the first time the entry is
+    // expanded. Clones share them so rebuilding the list doesn't check them again.
+    validated: Arc<OnceLock<Vec<Reference>>>,
+}
+
+impl Entry {
+    /// An empty entry at time with a new id. Ids start with the time so the files sort
+    /// by date, and end with random digits so entries made in the same second differ.
+    pub fn new(time: NaiveDateTime) -> Entry {
+        Entry {
+            id: format!("{}-{:04x}", time.format("%Y%m%d-%H%M%S"), rand::random::<u16>()),
+            time,
+            title: None,
+            text: String::new(),
+            references: Vec::new(),
+            broken_references: Vec::new(),
+            validated: Arc::default(),
+        }
+    }
+
+    /// Fills in the title and text from what was written in an editor. The first line
+    /// is the title, with the #s of a markdown heading taken off, and the rest is the
+    /// text. An empty first line means there's no title. Returns false if nothing was
+    /// written.
+    pub fn set_written(&mut self, written: &str) -> bool {
+        if written.trim().is_empty() {
+            return false;
+        }
+        let written = written.trim_end();
+        let (first, rest) = match written.find('\n') {
+            Some(pos) => (&written[..pos], &written[pos + 1..]),
+            None => (written, ""),
+        };
+        let title = heading_text(first).trim();
+        self.title = if title.is_empty() { None } else { Some(title.to_string()) };
+        self.text = rest.trim().to_string();
+        true
+    }
+
+    /// The title and text the way they're written in an editor
+    pub fn written(&self) -> String {
+        match &self.title {
+            Some(title) => format!("# {}\n\n{}\n", title, self.text),
+            None => format!("\n{}\n", self.text),
+        }
+    }
+
+    /// The title, or the first line of the text for entries without one
+    pub fn heading(&self) -> &str {
+        match &self.title {
+            Some(title) => title,
+            None => self.text.lines().next().unwrap_or(""),
+        }
+    }
+
+    /// The #tags in the title and text
+    pub fn tags(&self) -> Vec<String> {
+        tags::parse_tags(&format!("{}\n{}", self.title.as_deref().unwrap_or(""), self.text))
+    }
+
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        self.tags().iter().any(|t| tags.contains(t))
+    }
+
+    /// The key: value lines at the end of the text
+    pub fn fields(&self) -> Vec<(String, String)> {
+        tags::parse_fields(&self.text)
+    }
+
+    pub fn field(&self, key: &str) -> Option<String> {
+        self.fields().into_iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn mood(&self) -> Option<u8> {
+        self.field("mood").and_then(|v| tags::parse_rating(&v))
+    }
+
+    pub fn energy(&self) -> Option<u8> {
+        self.field("energy").and_then(|v| tags::parse_rating(&v))
+    }
+
+    /// The whole file the entry is saved as
+    pub fn to_file(&self) -> String {
+        let mut references = String::new();
+        for reference in &self.references {
+            references.push_str(&format!("ref: {}\n", reference.to_line()));
+        }
+        for broken in &self.broken_references {
+            references.push_str(&format!("ref: {}\n", broken));
+        }
+        format!(
+            "---\nid: {}\ntime: {}\n{}---\n{}",
+            self.id,
+            self.time.format(TIME_FORMAT),
+            references,
+            self.written()
+        )
+    }
+
+    /// Reads an entry saved with to_file. Files edited by hand can leave out the id, in
+    /// which case id is used, and can have other keys in the front matter.
+    pub fn from_file(text: &str, id: &str) -> Result<Entry, Box<dyn Error>> {
+        let mut lines = text.lines();
+        if lines.next().map(|l| l.trim()) != Some("---") {
+            return Err(Box::new(crate::Error::new("Entry has no front matter")));
+        }
+
+        let mut found_id = None;
+        let mut time = None;
+        let mut references = Vec::new();
+        let mut broken_references = Vec::new();
+        for line in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            match key {
+                "id" => found_id = Some(value.to_string()),
+                "time" => time = Some(NaiveDateTime::parse_from_str(value, TIME_FORMAT)?),
+                "ref" => match Reference::parse(value) {
+                    Ok(reference) => references.push(reference),
+                    Err(_) => broken_references.push(value.to_string()),
+                },
+                _ => (),
+            }
+        }
+        let time = match time {
+            Some(t) => t,
+            None => return Err(Box::new(crate::Error::new("Entry has no time"))),
+        };
+
+        let mut entry = Entry::new(time);
+        entry.id = found_id.unwrap_or_else(|| id.to_string());
+        entry.references = references;
+        entry.broken_references = broken_references;
+        entry.set_written(&lines.collect::<Vec<&str>>().join("\n"));
+        Ok(entry)
+    }
+
+    /// Saves the entry in dir, replacing the file it had before. It's written to a
+    /// temporary file and renamed so a crash can't leave half an entry behind.
+    pub fn save(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        let temp = dir.join(format!(".{}.md.tmp", self.id));
+        fs::write(&temp, self.to_file())?;
+        fs::rename(&temp, entry_path(dir, &self.id))?;
+        Ok(())
+    }
+
+    /// Saves a new entry in dir. If another entry already has its id, like one written
+    /// for the same time, it gets a new id so nothing is overwritten.
+    pub fn create(&mut self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        while entry_path(dir, &self.id).exists() {
+            self.id = Entry::new(self.time).id;
+        }
+        self.save(dir)
+    }
+
+    /// Saves changes to an entry that's already in dir
+    pub fn update(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        if !entry_path(dir, &self.id).exists() {
+            return Err(Box::new(crate::Error::new(&format!("No entry {} to update", self.id))));
+        }
+        self.save(dir)
+    }
+
+    pub fn load(dir: &Path, id: &str) -> Result<Entry, Box<dyn Error>> {
+        Entry::from_file(&fs::read_to_string(entry_path(dir, id))?, id)
+    }
+
+    pub fn delete(dir: &Path, id: &str) -> Result<(), Box<dyn Error>> {
+        fs::remove_file(entry_path(dir, id))?;
+        Ok(())
+    }
+
+    /// Reads every entry saved in dir, oldest first. Markdown files without front
+    /// matter aren't entries, so they're skipped. Files that can't be read as entries,
+    /// like sync conflict copies, are skipped too and returned as messages so one bad
+    /// file doesn't hide the rest.
+    pub fn load_all(dir: &Path) -> Result<(Vec<Entry>, Vec<String>), Box<dyn Error>> {
+        let mut entries = Vec::new();
+        let mut skipped = Vec::new();
+        if !dir.exists() {
+            return Ok((entries, skipped));
+        }
+        for file in fs::read_dir(dir)? {
+            let path = file?.path();
+            if path.extension().is_none_or(|e| e != "md") {
+                continue;
+            }
+            let text = match fs::read_to_string(&path) {
+                Ok(t) => t,
+                Err(e) => {
+                    skipped.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+            if !text.starts_with("---") {
+                continue;
+            }
+            let id = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            match Entry::from_file(&text, &id) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => skipped.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        entries.sort_by_key(|e| e.time);
+        skipped.sort();
+        Ok((entries, skipped))
+    }
+}
+
+// a line without the #s of a markdown heading like "## Title", which always have a
+// space after them, so a #tag at the start stays a tag
+fn heading_text(line: &str) -> &str {
+    let line = line.trim_start();
+    let rest = line.trim_start_matches('#');
+    if rest.len() < line.len() && (rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+        rest
+    } else {
+        line
+    }
+}
+
+fn entry_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.md", id))
+}
+
+impl Expand for Entry {
+    fn expand(&self) -> (Vec<Box<dyn Expand>>, bool) {
+        self.expand_to(WRAP_WIDTH)
+    }
+
+    fn expand_to(&self, width: usize) -> (Vec<Box<dyn Expand>>, bool) {
+        let width = width.min(WRAP_WIDTH);
+        let mut folds: Vec<Box<dyn Expand>> = Vec::new();
+        // the heading is the first line when there's no title, so it isn't shown twice
+        let skip = if self.title.is_none() { 1 } else { 0 };
+        for line in wrap(&self.text, width).into_iter().skip(skip) {
+            folds.push(Box::new(EntryLine(line)));
+        }
+        for reference in self.validated_references() {
+            folds.push(Box::new(reference.clone()));
+        }
+        for broken in &self.broken_references {
+            folds.push(Box::new(BrokenReference(broken.clone())));
+        }
+        (folds, false)
+    }
+
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
+    fn is(&self, target: &str) -> bool {
+        target == self.target()
+    }
+}
+
+impl Entry {
+    /// The references with whether they can still be reached. They're only checked the
+    /// first time since it means opening repositories.
+    pub fn validated_references(&self) -> &[Reference] {
+        self.validated.get_or_init(|| {
+            let mut references = self.references.clone();
+            for reference in &mut references {
+                reference.validate();
+            }
+            references
+        })
+    }
+
+    /// What FoldingList::reveal is given to find this entry
+    pub fn target(&self) -> String {
+        format!("entry {}", self.id)
+    }
+
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        let time = self.time.format("%H:%M").to_string();
+        write_row(stream, colors, width, &[("default", &time), ("default", " "), ("entry", self.heading())])
+    }
+}
+
+/// A line of an entry's text, shown when the entry is expanded
+#[derive(Debug, Clone)]
+struct EntryLine(String);
+
+impl Expand for EntryLine {
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        write_row(stream, Some(colors), width, &[("entry", &self.0)])
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        write_row(stream, None, width, &[("entry", &self.0)])
+    }
+}
+
+/// A commit an entry is about, optionally narrowed to a file and a range of its lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub repo_path: String,
+    pub commit: String,
+    pub path: Option<String>,
+    // first and last line, both included
+    pub lines: Option<(u32, u32)>,
+    // set by validate when the commit can't be reached anymore
+    pub missing: bool,
+    // the full id of the commit, set by validate when it can be reached
+    pub resolved: Option<String>,
+}
+
+impl Reference {
+    pub fn to_commit(commit: &CommitInfo) -> Reference {
+        Reference {
+            repo_path: commit.repo_path.clone(),
+            commit: commit.id.clone(),
+            path: None,
+            lines: None,
+            missing: false,
+            resolved: None,
+        }
+    }
+
+    /// Reads a ref: line of front matter. The repository, commit and optional file are
+    /// separated by " | " since paths can have spaces, and the file can end with a line
+    /// or range of lines like src/main.rs:10-20.
+    pub fn parse(value: &str) -> Result<Reference, Box<dyn Error>> {
+        let mut fields = value.split(" | ").map(|f| f.trim());
+        let (repo_path, commit) = match (fields.next(), fields.next()) {
+            (Some(repo), Some(commit)) if !repo.is_empty() && !commit.is_empty() => (repo, commit),
+            _ => {
+                let message = format!("Reference \"{}\" needs a repository and a commit", value);
+                return Err(Box::new(crate::Error::new(&message)));
+            }
+        };
+        if !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+            let message = format!("Reference \"{}\" has a commit that isn't a hex id", value);
+            return Err(Box::new(crate::Error::new(&message)));
+        }
+        let mut reference = Reference {
+            repo_path: repo_path.to_string(),
+            commit: commit.to_string(),
+            path: None,
+            lines: None,
+            missing: false,
+            resolved: None,
+        };
+
+        if let Some(file) = fields.next().filter(|f| !f.is_empty()) {
+            let range = file.rsplit_once(':').filter(|(_, r)| r.chars().all(|c| c.is_ascii_digit() || c == '-'));
+            let path = match range {
+                Some((path, range)) if !range.is_empty() => {
+                    reference.lines = Some(parse_lines(range)?);
+                    path
+                }
+                _ => file,
+            };
+            reference.path = Some(path.to_string());
+        }
+        Ok(reference)
+    }
+
+    /// The value of the ref: line the reference is saved as
+    pub fn to_line(&self) -> String {
+        match &self.path {
+            Some(_) => format!("{} | {} | {}", self.repo_path, self.commit, self.location()),
+            None => format!("{} | {}", self.repo_path, self.commit),
+        }
+    }
+
+    /// The file and lines, like src/main.rs:10-20
+    pub fn location(&self) -> String {
+        let path = self.path.as_deref().unwrap_or("");
+        match self.lines {
+            Some((first, last)) if first == last => format!("{}:{}", path, first),
+            Some((first, last)) => format!("{}:{}-{}", path, first, last),
+            None => path.to_string(),
+        }
+    }
+
+    /// Checks that the commit can still be reached from a branch, tag or HEAD of its
+    /// repository and still has the file, and finds its full id. Rewriting history
+    /// leaves the old commits around for a while, so just finding the commit isn't enough.
+    /// HEAD and local branches are checked before tags and remote branches since most
+    /// commits are on them.
+    pub fn validate(&mut self) {
+        self.resolved = self.reachable().unwrap_or(None);
+        self.missing = self.resolved.is_none();
+    }
+
+    // the full id of the commit if it can still be reached
+    fn reachable(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let repo = Repository::open(&self.repo_path)?;
+        let commit = repo.revparse_single(&self.commit)?.peel_to_commit()?;
+        if let Some(path) = &self.path {
+            if commit.tree()?.get_path(Path::new(path)).is_err() {
+                return Ok(None);
+            }
+        }
+
+        let mut tips = Vec::new();
+        if let Ok(head) = repo.head() {
+            tips.extend(head.target());
+        }
+        let mut others = Vec::new();
+        for reference in repo.references()? {
+            let reference = reference?;
+            if let Ok(tip) = reference.peel_to_commit() {
+                if reference.is_branch() {
+                    tips.push(tip.id());
+                } else {
+                    others.push(tip.id());
+                }
+            }
+        }
+        tips.extend(others);
+
+        let mut checked = HashSet::new();
+        for tip in tips {
+            if !checked.insert(tip) {
+                continue;
+            }
+            if tip == commit.id() || repo.graph_descendant_of(tip, commit.id())? {
+                return Ok(Some(commit.id().to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        let repo = Path::new(&self.repo_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.repo_path.clone());
+        let short_id = &self.commit[..self.commit.len().min(7)];
+        let location = match &self.path {
+            Some(_) => format!(" {}", self.location()),
+            None => String::new(),
+        };
+        let missing = if self.missing { " (missing)" } else { "" };
+        write_row(
+            stream,
+            colors,
+            width,
+            &[
+                ("entry", "-> "),
+                ("modify", short_id),
+                ("default", " "),
+                ("default", &repo),
+                ("default", &location),
+                ("delete", missing),
+            ],
+        )
+    }
+}
+
+// a line number or a range like 10-20
+fn parse_lines(range: &str) -> Result<(u32, u32), Box<dyn Error>> {
+    let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (first.parse()?, last.parse()?),
+        None => {
+            let line = range.parse()?;
+            (line, line)
+        }
+    };
+    if first > last {
+        return Err(Box::new(crate::Error::new(&format!("Line range {} is backwards", range))));
+    }
+    Ok((first, last))
+}
+
+impl Expand for Reference {
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
+    fn link(&self) -> Option<String> {
+        self.resolved.as_ref().map(|id| format!("commit {}", id))
+    }
+}
+
+/// A ref: line that couldn't be read, shown so it can be fixed
+#[derive(Debug, Clone)]
+struct BrokenReference(String);
+
+impl BrokenReference {
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        write_row(stream, colors, width, &[("entry", "-> "), ("delete", "can't read reference "), ("default", &self.0)])
+    }
+}
+
+impl Expand for BrokenReference {
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+}
+
+/// Breaks each line of text at spaces so none are longer than width. Words longer than
+/// width are split, and blank lines are kept so paragraphs stay apart.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut wrapped = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        let mut len = 0;
+        for word in line.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            if len > 0 && len + 1 + word.len() > width {
+                wrapped.push(std::mem::take(&mut current));
+                len = 0;
+            }
+            if len > 0 {
+                current.push(' ');
+                len += 1;
+            }
+            while word.len() > width - len {
+                let rest = word.split_off(width - len);
+                current.extend(word);
+                wrapped.push(std::mem::take(&mut current));
+                len = 0;
+                word = rest;
+            }
+            len += word.len();
+            current.extend(word);
+        }
+        wrapped.push(current);
+    }
+    wrapped
+}
+
+/// Opens text in $VISUAL or $EDITOR, falling back to vi, and returns what was saved
+pub fn edit_text(text: &str) -> Result<String, Box<dyn Error>> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    edit_with(&editor, text)
+}
+
+fn edit_with(editor: &str, text: &str) -> Result<String, Box<dyn Error>> {
+    let (path, mut file) = create_temp_file()?;
+    let written = file.write_all(text.as_bytes());
+    drop(file);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&path);
+        return Err(Box::new(e));
+    }
+
+    // the editor can have arguments in it like "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = match process::Command::new(program).args(words).arg(&path).status() {
+        Ok(status) => status,
+        Err(e) => {
+            let _ = fs::remove_file(&path);
+            return Err(Box::new(e));
+        }
+    };
+    // the file is kept so nothing written in an editor that crashed is lost
+    if !status.success() {
+        return Err(Box::new(crate::Error::new(&format!(
+            "{} exited with {}, the text is in {}",
+            editor,
+            status,
+            path.display()
+        ))));
+    }
+    let written = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    Ok(written?)
+}
+
+// a new file in the temp directory only this user can read. It's never one that was
+// already there, so a link left at the name can't send the text somewhere else.
+fn create_temp_file() -> Result<(PathBuf, fs::File), Box<dyn Error>> {
+    loop {
+        let name = format!("mmcj_entry_{}_{:08x}.md", process::id(), rand::random::<u32>());
+        let path = env::temp_dir().join(name);
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path);
+        match file {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(Box::new(e)),
+        }
+    }
+}
+
+/// Opens a new entry at time with the given references in an editor and saves it to
+/// the entries path. Returns None if nothing was written.
+pub fn write_entry(time: NaiveDateTime, references: Vec<Reference>) -> Result<Option<Entry>, Box<dyn Error>> {
+    let mut entry = Entry::new(time);
+    entry.references = references;
+    if !entry.set_written(&edit_text("")?) {
+        return Ok(None);
+    }
+    entry.create(Path::new(&get_entries_path()?))?;
+    Ok(Some(entry))
+}
+
+/// Opens an entry in an editor and saves the changes. Returns false if everything
+/// was deleted, which leaves the entry as it was.
+pub fn edit_entry(entry: &mut Entry) -> Result<bool, Box<dyn Error>> {
+    let mut edited = entry.clone();
+    if !edited.set_written(&edit_text(&entry.written())?) {
+        return Ok(false);
+    }
+    edited.update(Path::new(&get_entries_path()?))?;
+    *entry = edited;
+    Ok(true)
+}
+
+/// When an entry for a journal day is set: right now for today, otherwise the middle
+/// of the day so it sorts in with that day's work
+pub fn entry_time(day: chrono::NaiveDate, day_change_time: i32) -> NaiveDateTime {
+    let now = Local::now().naive_local();
+    if crate::timeline::journal_day_naive(&now, day_change_time) == day {
+        now
+    } else {
+        day.and_hms(12, 0, 0)
+    }
+}
+
+pub fn get_entries_path() -> Result<String, Box<dyn Error>> {
+    let mut path = crate::config::get_base_path()?;
+    path.push_str("entries/");
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{commit_at, test_repo};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn set_written_test() {
+        let mut entry = Entry::new(NaiveDate::from_ymd(2019, 12, 20).and_hms(14, 3, 0));
+        assert!(entry.id.starts_with("20191220-140300-"));
+        assert!(!entry.set_written("  \n\n"));
+
+        assert!(entry.set_written("# Fixed the folding list\n\nIt was the segments.\nAll of them.\n"));
+        assert_eq!(Some(String::from("Fixed the folding list")), entry.title);
+        assert_eq!("It was the segments.\nAll of them.", entry.text);
+        assert_eq!("# Fixed the folding list\n\nIt was the segments.\nAll of them.\n", entry.written());
+
+        assert!(entry.set_written("Just a title"));
+        assert_eq!("Just a title", entry.heading());
+        assert_eq!("", entry.text);
+
+        assert!(entry.set_written("## Subheading"));
+        assert_eq!(Some(String::from("Subheading")), entry.title);
+        assert!(entry.set_written("#debugging all day\n\nStill broken"));
+        assert_eq!(Some(String::from("#debugging all day")), entry.title);
+        assert_eq!(vec!["debugging"], entry.tags());
+        assert!(entry.set_written(&entry.written()));
+        assert_eq!(Some(String::from("#debugging all day")), entry.title);
+    }
+
+    #[test]
+    fn from_file_test() -> Result<(), Box<dyn Error>> {
+        let text = "---\r\ntime: 2019-12-20 14:03:00\r\nsynced: yes\r\n---\r\n# Edited by hand\r\n\r\nBody\r\n";
+        let entry = Entry::from_file(text, "by-hand")?;
+        assert_eq!("by-hand", entry.id);
+        assert_eq!(NaiveDate::from_ymd(2019, 12, 20).and_hms(14, 3, 0), entry.time);
+        assert_eq!("Edited by hand", entry.heading());
+        assert_eq!("Body", entry.text);
+
+        assert_eq!(entry, Entry::from_file(&entry.to_file(), "other")?);
+
+        let text = "---\ntime: 2019-12-20 14:03:00\n\
+                    ref: /code/my repo | 3f2a9c1 | src/main.rs:10-20\n\
+                    ref: /code/mmcj | abcdef0\n---\nBody";
+        let entry = Entry::from_file(text, "refs")?;
+        assert_eq!(2, entry.references.len());
+        assert_eq!("/code/my repo", entry.references[0].repo_path);
+        assert_eq!(Some(String::from("src/main.rs")), entry.references[0].path);
+        assert_eq!(Some((10, 20)), entry.references[0].lines);
+        assert_eq!(None, entry.references[1].path);
+
+        // a bad reference doesn't lose the entry or itself
+        let text = "---\ntime: 2019-12-20 14:03:00\nref: /code/mmcj\nref: /code/mmcj | abcdef0\n---\nBody";
+        let entry = Entry::from_file(text, "bad-ref")?;
+        assert_eq!(1, entry.references.len());
+        assert_eq!(vec!["/code/mmcj"], entry.broken_references);
+        assert!(entry.to_file().contains("ref: /code/mmcj\n"));
+        assert_eq!(2, entry.expand().0.len());
+        assert!(Entry::from_file("# No front matter", "id").is_err());
+        assert!(Entry::from_file("---\nid: x\n---\nNo time", "id").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn save_test() -> Result<(), Box<dyn Error>> {
+        let dir = env::temp_dir().join("mmcj_entry_test");
+        let _ = fs::remove_dir_all(&dir);
+        let mut later = Entry::new(NaiveDate::from_ymd(2019, 12, 21).and_hms(9, 0, 0));
+        later.set_written("Later\n\nSecond");
+        let mut earlier = Entry::new(NaiveDate::from_ymd(2019, 12, 20).and_hms(14, 3, 0));
+        earlier.set_written("\nNo title here");
+        assert_eq!(None, earlier.title);
+        assert!(later.update(&dir).is_err(), "Entries have to be saved before they're updated");
+
+        // entries for the same past day all get the same time, so ids can be the same
+        let mut first = Entry::new(NaiveDate::from_ymd(2019, 12, 18).and_hms(12, 0, 0));
+        first.set_written("First");
+        let mut second = first.clone();
+        second.set_written("Second");
+        first.create(&dir)?;
+        second.create(&dir)?;
+        assert_ne!(first.id, second.id);
+        assert_eq!(first, Entry::load(&dir, &first.id)?);
+        Entry::delete(&dir, &first.id)?;
+        Entry::delete(&dir, &second.id)?;
+
+        later.save(&dir)?;
+        earlier.save(&dir)?;
+        fs::write(dir.join("notes.txt"), "not an entry")?;
+        fs::write(dir.join("README.md"), "# Not an entry either")?;
+        fs::write(dir.join("broken.md"), "---\ntime: yesterday\n---\nBad time")?;
+        let (entries, skipped) = Entry::load_all(&dir)?;
+        assert_eq!(vec![earlier.clone(), later.clone()], entries);
+        assert_eq!(1, skipped.len());
+        assert!(skipped[0].contains("broken.md"));
+        fs::remove_file(dir.join("broken.md"))?;
+
+        // the id stays the same when the time moves
+        later.time = NaiveDate::from_ymd(2019, 12, 19).and_hms(9, 0, 0);
+        later.update(&dir)?;
+        assert_eq!(later, Entry::load(&dir, &later.id)?);
+        assert_eq!(vec![later.clone(), earlier.clone()], Entry::load_all(&dir)?.0);
+
+        Entry::delete(&dir, &later.id)?;
+        assert_eq!(vec![earlier], Entry::load_all(&dir)?.0);
+        assert!(Entry::delete(&dir, &later.id).is_err());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reference_test() -> Result<(), Box<dyn Error>> {
+        let reference = Reference::parse("/code/mmcj | abcdef0 | notes: day one.md:7")?;
+        assert_eq!(Some(String::from("notes: day one.md")), reference.path);
+        assert_eq!(Some((7, 7)), reference.lines);
+        assert_eq!("/code/mmcj | abcdef0 | notes: day one.md:7", reference.to_line());
+        assert_eq!(None, Reference::parse("/code/mmcj | abcdef0 | C:")?.lines);
+        assert!(Reference::parse("/code/mmcj").is_err());
+        assert!(Reference::parse("/code/mmcj | abcdef0 | a.rs:20-10").is_err());
+        assert!(Reference::parse("/code/mmcj | é0a").is_err(), "Commits should be hex ids");
+
+        let (dir, repo) = test_repo("mmcj_reference_test")?;
+        let first = commit_at(&repo, 1_576_800_000, "first", &[("a.rs", "fn main() {}\n")])?;
+        let first_commit = repo.find_commit(first)?;
+        let (sig, tree) = (first_commit.author(), first_commit.tree()?);
+        // a commit that no branch leads to, like the old one after a rebase
+        let dropped = repo.commit(None, &sig, &sig, "dropped", &tree, &[&first_commit])?;
+        let tagged = repo.commit(None, &sig, &sig, "tagged", &tree, &[&first_commit])?;
+        repo.reference("refs/tags/v1", tagged, false, "tag")?;
+
+        let repo_path = dir.to_string_lossy();
+        let short_id = &first.to_string()[..7];
+        let mut found = Reference::parse(&format!("{} | {} | a.rs:1", repo_path, short_id))?;
+        let mut no_file = Reference::parse(&format!("{} | {} | b.rs", repo_path, first))?;
+        let mut rewritten = Reference::parse(&format!("{} | {}", repo_path, dropped))?;
+        let mut no_repo = Reference::parse(&format!("{}/nothing | {}", repo_path, first))?;
+        let mut on_tag = Reference::parse(&format!("{} | {}", repo_path, tagged))?;
+        for reference in [&mut found, &mut no_file, &mut rewritten, &mut no_repo, &mut on_tag] {
+            reference.validate();
+        }
+        assert!(!found.missing);
+        assert!(!on_tag.missing);
+        assert_eq!(Some(format!("commit {}", first)), found.link(), "Short ids should link to the full id");
+        assert!(no_file.missing);
+        assert!(rewritten.missing);
+        assert!(no_repo.missing);
+        assert_eq!(None, no_repo.link());
+
+        // an entry's references are checked once, the first time it's expanded
+        let mut entry = Entry::new(NaiveDate::from_ymd(2019, 12, 20).and_hms(14, 3, 0));
+        entry.set_written("# Found it");
+        entry.references.push(Reference::parse(&format!("{} | {}", repo_path, first))?);
+        assert_eq!(1, entry.expand().0.len());
+        let shown = entry.clone();
+        fs::remove_dir_all(&dir)?;
+        assert!(!shown.validated_references()[0].missing);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wrap_test() {
+        let text = "It was the segments all along.\n\nSupercalifragilistic";
+        assert_eq!(
+            vec!["It was the", "segments", "all along.", "", "Supercalif", "ragilistic"],
+            wrap(text, 10)
+        );
+        assert_eq!(vec!["It was the segments all along.", "", "Supercalifragilistic"], wrap(text, 80));
+        assert!(wrap("", 10).is_empty());
+
+        let mut entry = Entry::new(NaiveDate::from_ymd(2019, 12, 20).and_hms(14, 3, 0));
+        entry.set_written("# Title\n\nFirst\n\nSecond");
+        assert_eq!(3, entry.expand().0.len());
+        entry.set_written("\nFirst\n\nSecond");
+        assert_eq!(2, entry.expand().0.len());
+        entry.set_written("# Title\n\nIt was the segments all along.");
+        assert_eq!(3, entry.expand_to(10).0.len());
+    }
+
+    #[test]
+    fn edit_with_test() -> Result<(), Box<dyn Error>> {
+        assert_eq!("# Kept as is", edit_with("true", "# Kept as is")?);
+
+        // a failed editor leaves what was written in the file it names
+        let message = edit_with("false", "# Half written").unwrap_err().to_string();
+        let path = message.split(" the text is in ").nth(1).unwrap_or_default();
+        assert_eq!("# Half written", fs::read_to_string(path)?);
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn entry_time_test() {
+        let day = NaiveDate::from_ymd(2019, 12, 20);
+        assert_eq!(day.and_hms(12, 0, 0), entry_time(day, 500));
+    }
 }
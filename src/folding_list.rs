@@ -1,5 +1,6 @@
 use super::Error;
 use crate::{Colors, LineCounts};
+use chrono::NaiveDate;
 use std::{error, fmt::Write};
 
 const DEBUG: bool = false;
@@ -9,6 +10,8 @@ pub struct FoldingList {
     expanded: Vec<Segment>,
     cursor: usize,
     segment: usize, // segment that that cursor is currently in
+    indent: String, // drawn once for every level a line is nested
+    width: u16,     // of the terminal when the list was last drawn
 }
 
 /// This structure stores the start and ends of a currently expanded segment.
@@ -22,6 +25,10 @@ pub trait Expand {
     fn expand(&self) -> (Vec<Box<dyn Expand>>, bool) {
         (Vec::new(), false)
     }
+    /// Like expand, for lines that lay out their children to fit width columns
+    fn expand_to(&self, _width: usize) -> (Vec<Box<dyn Expand>>, bool) {
+        self.expand()
+    }
     fn display(
         &self,
         _stream: &mut String,
@@ -46,6 +53,22 @@ pub trait Expand {
     fn id(&self) -> usize {
         0
     } // mostly for testing
+    /// The journal day the line is for, if it's for one
+    fn date(&self) -> Option<NaiveDate> {
+        None
+    }
+    /// Whether the line is the one named by target, like "commit <id>"
+    fn is(&self, _target: &str) -> bool {
+        false
+    }
+    /// Whether the target is somewhere under the line once it's expanded
+    fn holds(&self, _target: &str) -> bool {
+        false
+    }
+    /// Where following the line goes, as a target for reveal
+    fn link(&self) -> Option<String> {
+        None
+    }
 }
 
 impl FoldingList {
@@ -62,9 +85,93 @@ impl FoldingList {
             }],
             cursor: 0,
             segment: 0,
+            indent: String::new(),
+            width: 80,
         })
     }
 
+    pub fn set_indent(&mut self, indent: &str) {
+        self.indent = indent.to_string();
+    }
+
+    /// The width lines are laid out for until the list is drawn
+    pub fn set_width(&mut self, width: u16) {
+        self.width = width;
+    }
+
+    // columns left for the children of the line at index after their indent
+    fn child_width(&self, index: usize) -> usize {
+        let indent = self.indent.chars().count() * (self.depth(index) + 1);
+        // rows leave a column free before the line counts
+        (self.width as usize).saturating_sub(indent + 1)
+    }
+
+    /// How many segments deep the line at index is nested, the root segment being 0
+    fn depth(&self, index: usize) -> usize {
+        self.expanded
+            .iter()
+            .filter(|seg| index >= seg.start && index < seg.end)
+            .count()
+            .saturating_sub(1)
+    }
+
+    // the line that was expanded to show the line at index
+    fn parent(&self, index: usize) -> Option<usize> {
+        self.expanded
+            .iter()
+            .filter(|seg| seg.start > 0 && index >= seg.start && index < seg.end)
+            .map(|seg| seg.start)
+            .max()
+            .map(|start| start - 1)
+    }
+
+    pub fn selected(&self) -> &dyn Expand {
+        self.list[self.cursor].as_ref()
+    }
+
+    /// The date of the selected line or of the closest line it's under that has one
+    pub fn selected_date(&self) -> Option<NaiveDate> {
+        let mut index = Some(self.cursor);
+        while let Some(i) = index {
+            if let Some(date) = self.list[i].date() {
+                return Some(date);
+            }
+            index = self.parent(i);
+        }
+        None
+    }
+
+    /// Whether the selected line is the target or is somewhere under it
+    pub fn selected_under(&self, target: &str) -> bool {
+        let mut index = Some(self.cursor);
+        while let Some(i) = index {
+            if self.list[i].is(target) {
+                return true;
+            }
+            index = self.parent(i);
+        }
+        false
+    }
+
+    /// Expands every line leading to the target and moves the cursor to it. Returns
+    /// false if it isn't in the list.
+    pub fn reveal(&mut self, target: &str) -> bool {
+        let mut index = 0;
+        // expanding puts the children right after a line, so they're searched next
+        while index < self.list.len() {
+            if self.list[index].is(target) {
+                self.jump(index);
+                return true;
+            }
+            if self.list[index].holds(target) {
+                self.jump(index);
+                self.expand();
+            }
+            index += 1;
+        }
+        false
+    }
+
     pub fn scroll(&mut self, amount: i64) {
         let pos = self.cursor as i64 + amount;
         if pos <= 0 {
@@ -123,7 +230,7 @@ impl FoldingList {
         }
 
         // expand the selected segment
-        let (mut to_insert, recursive) = self.list[self.cursor].expand();
+        let (mut to_insert, recursive) = self.list[self.cursor].expand_to(self.child_width(self.cursor));
         let insert_len = to_insert.len();
 
         // if insert is empty no more work is needed and new segment shouldn't be created
@@ -192,6 +299,7 @@ impl FoldingList {
         // TODO: make scroll customizable
         // TODO: make application width aware
         let (width, height) = termion::terminal_size().unwrap();
+        self.width = width;
         let midpoint = height as usize / 2;
         let mut index = if self.list.len() < height as usize {
             0
@@ -212,10 +320,12 @@ impl FoldingList {
 
         for line in 1..(height + 1) {
             let lc = self.list[index].counts();
+            let indent = self.indent.repeat(self.depth(index));
             let adj_width = match &lc {
-                Some(c) => width - c.char_width(),
+                Some(c) => width.saturating_sub(c.char_width()),
                 None => width,
             };
+            let adj_width = adj_width.saturating_sub(indent.chars().count() as u16);
 
             if index == self.cursor {
                 write!(
@@ -225,6 +335,7 @@ impl FoldingList {
                     termion::clear::CurrentLine,
                     colors.fg("highlight"),
                 )?;
+                stream.push_str(&indent);
                 self.list[index].highlight(&mut stream, &colors, line, adj_width)?;
                 if let Some(c) = &lc {
                     c.highlight(&mut stream, &colors, line, width)?;
@@ -237,6 +348,7 @@ impl FoldingList {
                     termion::clear::CurrentLine,
                     colors.fg("default"),
                 )?;
+                stream.push_str(&indent);
                 self.list[index].display(&mut stream, &colors, line, adj_width)?;
                 if let Some(c) = &lc {
                     c.display(&mut stream, &colors, line, width)?;
@@ -294,6 +406,14 @@ mod tests {
         fn id(&self) -> usize {
             self.id
         }
+        fn is(&self, target: &str) -> bool {
+            target == self.id.to_string()
+        }
+        fn holds(&self, target: &str) -> bool {
+            // children are the next two ids, so everything two levels down is within 4
+            let target: usize = target.parse().unwrap_or(0);
+            target > self.id && target <= self.id + 4
+        }
     }
 
     fn new_test_list() -> FoldingList {
@@ -462,6 +582,42 @@ mod tests {
         assert_eq!(1, fl.expanded.len());
     }
 
+    #[test]
+    fn depth_test() {
+        let mut fl = new_test_list();
+        fl.jump(1);
+        fl.expand();
+        fl.jump(2);
+        fl.expand();
+        assert_eq!(0, fl.depth(0));
+        assert_eq!(1, fl.depth(2));
+        assert_eq!(2, fl.depth(3));
+        assert_eq!(1, fl.depth(5));
+        assert_eq!(0, fl.depth(6));
+
+        fl.set_indent("    ");
+        fl.set_width(40);
+        assert_eq!(35, fl.child_width(0));
+        assert_eq!(27, fl.child_width(3));
+    }
+
+    #[test]
+    fn reveal_test() {
+        let mut fl = new_test_list();
+        assert!(fl.reveal("12"));
+        assert_eq!(12, fl.selected().id());
+        assert!(fl.selected_under("10"));
+        assert!(!fl.selected_under("20"));
+        assert_eq!(Some(2), fl.parent(fl.cursor));
+        assert_eq!(Some(1), fl.parent(2));
+        assert_eq!(None, fl.parent(1));
+        assert_eq!(None, fl.selected_date());
+
+        assert!(fl.reveal("30"));
+        assert_eq!(30, fl.list[fl.cursor].id());
+        assert!(!fl.reveal("99"));
+    }
+
     #[test]
     fn monkey_test() {
         // nothing in here should cause panic
@@ -0,0 +1,55 @@
+/// Matches text against a glob pattern. `?` matches one character, `*` matches any
+/// characters except `/` and `**` matches any characters including `/`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // "**/" also matches no directories at all
+            if rest.first() == Some(&'/') && match_from(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|skip| match_from(rest, &text[skip..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for skip in 0..=text.len() {
+                if match_from(rest, &text[skip..]) {
+                    return true;
+                }
+                if skip < text.len() && text[skip] == '/' {
+                    return false;
+                }
+            }
+            false
+        }
+        Some('?') => !text.is_empty() && match_from(&pattern[1..], &text[1..]),
+        Some(ch) => text.first() == Some(ch) && match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_test() {
+        assert!(glob_match("*@ourcompany.com", "aaron@ourcompany.com"));
+        assert!(!glob_match("*@ourcompany.com", "aaron@ourcompany.com.au"));
+        assert!(glob_match("work/*", "work/mmcj"));
+        assert!(!glob_match("work/*", "work/mmcj/nested"));
+        assert!(glob_match("work/**", "work/mmcj/nested"));
+        assert!(glob_match("**/node_modules", "node_modules"));
+        assert!(glob_match("**/node_modules", "web/app/node_modules"));
+        assert!(!glob_match("**/node_modules", "web/node_modules_old"));
+        assert!(glob_match("mmcj_?", "mmcj_2"));
+        assert!(!glob_match("mmcj_?", "mmcj_"));
+        assert!(glob_match("*", ""));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::glob::glob_match;
+use git2::Repository;
+use std::fs;
+
+/// The emails and names commits are matched against. Both can be globs like
+/// `*@ourcompany.com` and are compared ignoring case.
+#[derive(Debug, Clone)]
+pub struct Identities {
+    emails: Vec<String>,
+    names: Vec<String>,
+}
+
+impl Identities {
+    pub fn new(emails: &[String], names: &[String]) -> Identities {
+        Identities {
+            emails: emails.iter().map(|e| e.to_lowercase()).collect(),
+            names: names.iter().map(|n| n.to_lowercase()).collect(),
+        }
+    }
+
+    /// A copy with more emails to match
+    pub fn with_emails(&self, emails: &[String]) -> Identities {
+        let mut identities = self.clone();
+        identities.emails.extend(emails.iter().map(|e| e.to_lowercase()));
+        identities
+    }
+
+    pub fn matches(&self, name: &str, email: &str) -> bool {
+        let name = name.to_lowercase();
+        let email = email.to_lowercase();
+        self.emails.iter().any(|p| glob_match(p, &email)) || self.names.iter().any(|p| glob_match(p, &name))
+    }
+}
+
+/// The name and email in each `Co-authored-by:` trailer of a commit message
+pub fn co_authors(message: &str) -> Vec<(String, String)> {
+    const TRAILER: &str = "co-authored-by:";
+    let mut found = Vec::new();
+    for line in message.lines() {
+        let line = line.trim();
+        // get instead of slicing since the line may not be ascii
+        match line.get(..TRAILER.len()) {
+            Some(key) if key.eq_ignore_ascii_case(TRAILER) => (),
+            _ => continue,
+        }
+        let value = &line[TRAILER.len()..];
+        if let (Some(open), Some(close)) = (value.find('<'), value.rfind('>')) {
+            if open < close {
+                found.push((value[..open].trim().to_string(), value[open + 1..close].trim().to_string()));
+            }
+        }
+    }
+    found
+}
+
+/// Maps the names and emails in commits to the proper ones using a repository's .mailmap
+#[derive(Debug, Clone, Default)]
+pub struct Mailmap {
+    entries: Vec<MailmapEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct MailmapEntry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    // lowercase so lookups ignore case
+    commit_name: Option<String>,
+    commit_email: String,
+}
+
+impl Mailmap {
+    /// Reads .mailmap from the working directory, or from HEAD for bare repositories.
+    /// A repository without one gets an empty mailmap.
+    pub fn from_repo(repo: &Repository) -> Mailmap {
+        if let Some(dir) = repo.workdir() {
+            if let Ok(text) = fs::read_to_string(dir.join(".mailmap")) {
+                return Mailmap::parse(&text);
+            }
+        }
+        let blob = repo
+            .head()
+            .and_then(|head| head.peel_to_tree())
+            .and_then(|tree| tree.get_path(std::path::Path::new(".mailmap")))
+            .and_then(|entry| repo.find_blob(entry.id()));
+        match blob {
+            Ok(blob) => Mailmap::parse(&String::from_utf8_lossy(blob.content())),
+            Err(_) => Mailmap::default(),
+        }
+    }
+
+    /// Parses lines in any of the forms git understands:
+    ///
+    /// ```text
+    /// Proper Name <commit@email>
+    /// <proper@email> <commit@email>
+    /// Proper Name <proper@email> <commit@email>
+    /// Proper Name <proper@email> Commit Name <commit@email>
+    /// ```
+    pub fn parse(text: &str) -> Mailmap {
+        let mut mailmap = Mailmap::default();
+        for line in text.lines() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+
+            // split into (name before the email, email) pairs
+            let mut pairs = Vec::new();
+            let mut rest = line;
+            while let (Some(open), Some(close)) = (rest.find('<'), rest.find('>')) {
+                if close < open {
+                    break;
+                }
+                let name = rest[..open].trim();
+                let name = if name.is_empty() { None } else { Some(name.to_string()) };
+                pairs.push((name, rest[open + 1..close].trim().to_string()));
+                rest = &rest[close + 1..];
+            }
+
+            let entry = match pairs.len() {
+                1 => MailmapEntry {
+                    proper_name: pairs[0].0.clone(),
+                    proper_email: None,
+                    commit_name: None,
+                    commit_email: pairs[0].1.to_lowercase(),
+                },
+                2 => MailmapEntry {
+                    proper_name: pairs[0].0.clone(),
+                    proper_email: Some(pairs[0].1.clone()),
+                    commit_name: pairs[1].0.as_ref().map(|n| n.to_lowercase()),
+                    commit_email: pairs[1].1.to_lowercase(),
+                },
+                _ => continue,
+            };
+            mailmap.entries.push(entry);
+        }
+        mailmap
+    }
+
+    /// The proper name and email for a commit's name and email. Entries that also give
+    /// the commit name win over ones that only give the email.
+    pub fn resolve(&self, name: &str, email: &str) -> (String, String) {
+        let lower_name = name.to_lowercase();
+        let lower_email = email.to_lowercase();
+        let found = self
+            .entries
+            .iter()
+            .filter(|e| e.commit_email == lower_email)
+            .filter(|e| e.commit_name.as_ref().is_none_or(|n| *n == lower_name))
+            .max_by_key(|e| e.commit_name.is_some());
+
+        match found {
+            Some(entry) => (
+                entry.proper_name.clone().unwrap_or_else(|| name.to_string()),
+                entry.proper_email.clone().unwrap_or_else(|| email.to_string()),
+            ),
+            None => (name.to_string(), email.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identities_test() {
+        let identities = Identities::new(
+            &[String::from("Celnardur@PM.com"), String::from("*@ourcompany.com")],
+            &[String::from("aaron dorrance")],
+        );
+        assert!(identities.matches("", "celnardur@pm.com"));
+        assert!(identities.matches("", "aaron@OurCompany.com"));
+        assert!(identities.matches("Aaron Dorrance", "aaron@old-laptop.local"));
+        assert!(!identities.matches("Someone Else", "celnardur@protonmail.com"));
+        assert!(identities
+            .with_emails(&[String::from("celnardur@protonmail.com")])
+            .matches("", "celnardur@protonmail.com"));
+    }
+
+    #[test]
+    fn co_authors_test() {
+        let message = "Pair on the folding list\n\n\
+                       Co-authored-by: Aaron Dorrance <celnardur@pm.com>\n\
+                       co-authored-by:Someone <someone@pm.com>\n\
+                       Co-authored-by: no email\n\
+                       Signed-off-by: Someone <someone@pm.com>\n";
+        assert_eq!(
+            vec![
+                (String::from("Aaron Dorrance"), String::from("celnardur@pm.com")),
+                (String::from("Someone"), String::from("someone@pm.com")),
+            ],
+            co_authors(message)
+        );
+        assert!(co_authors("Co-authored").is_empty());
+    }
+
+    #[test]
+    fn mailmap_test() {
+        let mailmap = Mailmap::parse(
+            "# comment\n\
+             Aaron Dorrance <celnardur@protonmail.com>\n\
+             <celnardur@protonmail.com> <Celnardur@old-laptop.local>\n\
+             Aaron Dorrance <celnardur@protonmail.com> Work Me <me@ourcompany.com>\n\
+             Someone <someone@pm.com> <me@ourcompany.com> # trailing comment\n",
+        );
+        assert_eq!(
+            (String::from("Aaron Dorrance"), String::from("celnardur@protonmail.com")),
+            mailmap.resolve("celnardur", "celnardur@protonmail.com")
+        );
+        assert_eq!(
+            (String::from("celnardur"), String::from("celnardur@protonmail.com")),
+            mailmap.resolve("celnardur", "celnardur@OLD-laptop.local")
+        );
+        assert_eq!(
+            (String::from("Aaron Dorrance"), String::from("celnardur@protonmail.com")),
+            mailmap.resolve("Work Me", "me@ourcompany.com")
+        );
+        assert_eq!(
+            (String::from("Someone"), String::from("someone@pm.com")),
+            mailmap.resolve("Other", "me@ourcompany.com")
+        );
+        assert_eq!(
+            (String::from("Nobody"), String::from("nobody@pm.com")),
+            mailmap.resolve("Nobody", "nobody@pm.com")
+        );
+    }
+}
@@ -1,14 +1,31 @@
-use git2::{BranchType, Commit, Repository, Revwalk};
+use git2::{Commit, Repository, Revwalk, Sort};
+use identity::{Identities, Mailmap};
+use scan::ScanOptions;
+use timeline::TimeWindow;
 
-use std::error;
+use std::{collections::HashSet, error};
 
 pub mod diffs;
+pub use diffs::CommitInfo;
 pub use diffs::JournalDiff;
 pub use diffs::LineCounts;
 
+pub mod cache;
+pub use cache::DiffCache;
+
+pub mod cli;
+pub use cli::Args;
+pub use cli::Command;
+
+pub mod discovery;
+pub mod glob;
+pub mod identity;
+
 pub mod config;
 pub use config::Colors;
 pub use config::Config;
+pub use config::RefSelection;
+pub use config::RepoSettings;
 
 pub mod entry;
 pub use entry::Entry;
@@ -17,35 +34,118 @@ pub mod folding_list;
 pub use folding_list::Expand;
 pub use folding_list::FoldingList;
 
+pub mod scan;
+
+pub mod setup;
+
+pub mod tags;
+pub use tags::TagIndex;
+
+pub mod timeline;
+
+#[cfg(test)]
+mod testing;
+
 // General Functions
 
 pub fn get_repo_revwalk<'repo>(
     repo: &'repo Repository,
+) -> Result<Revwalk<'repo>, Box<dyn error::Error>> {
+    get_branch_revwalk(repo, &[])
+}
+
+/// Walks the local branches whose names match one of the globs, or every local branch
+/// if there are none
+pub fn get_branch_revwalk<'repo>(
+    repo: &'repo Repository,
+    branches: &[String],
+) -> Result<Revwalk<'repo>, Box<dyn error::Error>> {
+    get_ref_revwalk(repo, &RefSelection::default(), branches)
+}
+
+/// Walks the refs picked by the selection, with branches filtered by the globs, newest
+/// commit first. Refs pointing at the same commit are only pushed once and the walk
+/// itself never returns a commit twice.
+pub fn get_ref_revwalk<'repo>(
+    repo: &'repo Repository,
+    refs: &RefSelection,
+    branches: &[String],
 ) -> Result<Revwalk<'repo>, Box<dyn error::Error>> {
     let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TIME);
 
-    for branch in repo.branches(Some(BranchType::Local))? {
-        let oid = match branch?.0.get().resolve()?.target() {
-            Some(id) => id,
-            None => continue,
-        };
+    if refs.head_only {
+        // a new repository has no HEAD commit to walk from
+        if let Ok(commit) = repo.head().and_then(|head| head.peel_to_commit()) {
+            walk.push(commit.id())?;
+        }
+        return Ok(walk);
+    }
+
+    let mut pushed = HashSet::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        let name = String::from_utf8_lossy(reference.name_bytes()).into_owned();
+        if !refs.selects(&name, branches) {
+            continue;
+        }
 
-        walk.push(oid)?;
+        // tags can point at trees or blobs, which can't be walked
+        let oid = match reference.peel_to_commit() {
+            Ok(commit) => commit.id(),
+            Err(_) => continue,
+        };
+        if pushed.insert(oid) {
+            walk.push(oid)?;
+        }
     }
     Ok(walk)
 }
 
+/// Keeps the commits whose author, after applying the repository's .mailmap, has
+/// one of the emails. Emails can be globs and case is ignored.
 pub fn filter_by_email<'repo>(
     repo: &'repo Repository,
     walk: Revwalk,
-    emails: &Vec<String>,
+    emails: &[String],
+) -> Result<Vec<Commit<'repo>>, Box<dyn error::Error>> {
+    let identities = Identities::new(emails, &[]);
+    filter_by_identity(repo, walk, &identities, &Mailmap::from_repo(repo), &TimeWindow::all())
+}
+
+/// Keeps the commits in the time window whose author or one of its co-authors, after
+/// applying the mailmap, matches one of the identities by email or name.
+/// The walk should be sorted newest first so it can stop at the start of the window.
+pub fn filter_by_identity<'repo>(
+    repo: &'repo Repository,
+    walk: Revwalk,
+    identities: &Identities,
+    mailmap: &Mailmap,
+    window: &TimeWindow,
 ) -> Result<Vec<Commit<'repo>>, Box<dyn error::Error>> {
     let mut commits = Vec::new();
     for oid in walk {
         let commit = repo.find_commit(oid?)?;
-        let is_match = match commit.author().email() {
-            Some(e) => emails.contains(&String::from(e)),
-            None => continue,
+        // the walk is sorted by committer time, which is never before the author time
+        // unless a clock was off
+        if window.starts_after(commit.time().seconds()) {
+            break;
+        }
+        if !window.contains(commit.author().when().seconds()) {
+            continue;
+        }
+        let is_match = {
+            let author = commit.author();
+            let email = match author.email() {
+                Some(e) => e,
+                None => continue,
+            };
+            let (name, email) = mailmap.resolve(&String::from_utf8_lossy(author.name_bytes()), email);
+            identities.matches(&name, &email)
+                || identity::co_authors(&String::from_utf8_lossy(commit.message_bytes()))
+                    .iter()
+                    .map(|(name, email)| mailmap.resolve(name, email))
+                    .any(|(name, email)| identities.matches(&name, &email))
         };
 
         if is_match {
@@ -55,10 +155,88 @@ pub fn filter_by_email<'repo>(
     Ok(commits)
 }
 
+/// Diffs the commits in the time window in a repository by one of the identities,
+/// using the repository's own emails, refs, branches, path excludes and alias on top
+pub fn scan_repo(settings: &RepoSettings, options: &ScanOptions) -> Result<Vec<JournalDiff>, Box<dyn error::Error>> {
+    let repo = Repository::open(&settings.path)?;
+    let walk = get_ref_revwalk(&repo, &settings.refs, &settings.branches)?;
+
+    let identities = options.identities.with_emails(&settings.emails);
+    let mailmap = Mailmap::from_repo(&repo);
+    let commits = filter_by_identity(&repo, walk, &identities, &mailmap, &options.window)?;
+
+    let mut diffs = Vec::new();
+    for commit in commits {
+        if let Ok(mut journal_diff) = make_diff(&repo, &commit, options, &settings.exclude_paths) {
+            if let Some(info) = journal_diff.commit() {
+                let others = paired_with(info, &identities, &mailmap);
+                journal_diff.set_paired_with(others);
+            }
+            if let Some(alias) = &settings.alias {
+                journal_diff.set_repo_name(alias);
+            }
+            diffs.push(journal_diff);
+        }
+    }
+    Ok(diffs)
+}
+
+// the diff of a commit without the excluded paths, from the cache or saved there. Lazy
+// scans only keep its summary, or just git's stats if it isn't cached yet, and the
+// files are made when the commit is expanded.
+fn make_diff(
+    repo: &Repository,
+    commit: &Commit,
+    options: &ScanOptions,
+    exclude: &[String],
+) -> Result<JournalDiff, Box<dyn error::Error>> {
+    let id = commit.id().to_string();
+    let mut journal_diff = match options.cache.as_ref().and_then(|c| c.get(&id)) {
+        Some(mut cached) => {
+            cached.set_repo(repo);
+            cached
+        }
+        None if options.lazy => {
+            return JournalDiff::from_commit_lazy(repo, commit, exclude, options.cache.as_ref());
+        }
+        None => {
+            let journal_diff = JournalDiff::from_commit(repo, commit)?;
+            if let Some(cache) = &options.cache {
+                // a diff that can't be saved is only slower next time
+                let _ = cache.put(&id, &journal_diff);
+            }
+            journal_diff
+        }
+    };
+    journal_diff.exclude_paths(exclude);
+    if options.lazy {
+        return Ok(journal_diff.summary(exclude, options.cache.as_ref()));
+    }
+    Ok(journal_diff)
+}
+
+// the names of everyone else on a co-authored commit
+fn paired_with(commit: &CommitInfo, identities: &Identities, mailmap: &Mailmap) -> Vec<String> {
+    if commit.co_authors.is_empty() {
+        return Vec::new();
+    }
+    let mut names: Vec<String> = Vec::new();
+    let people = std::iter::once((&commit.author.name, &commit.author.email))
+        .chain(commit.co_authors.iter().map(|c| (&c.name, &c.email)));
+    for (name, email) in people {
+        let (name, email) = mailmap.resolve(name, email);
+        if !identities.matches(&name, &email) && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
 // Tests for General Functions
 #[cfg(test)]
 mod tests {
     use super::*;
+    use testing::{commit_as, commit_at, test_repo};
 
     #[test]
     fn get_repo_revwalk_test() -> Result<(), Box<dyn error::Error>> {
@@ -72,6 +250,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn get_branch_revwalk_test() -> Result<(), Box<dyn error::Error>> {
+        let repo = Repository::open("mmcj_test_repo")?;
+        let walk = get_branch_revwalk(&repo, &[String::from("no-such-branch-*")])?;
+        assert_eq!(0, walk.count(), "No branches should match");
+
+        let walk = get_branch_revwalk(&repo, &[String::from("*")])?;
+        assert_eq!(12, walk.count(), "Every branch should match *");
+        Ok(())
+    }
+
     #[test]
     fn filter_by_email_test() -> Result<(), Box<dyn error::Error>> {
         let repo = Repository::open("mmcj_test_repo")?;
@@ -98,6 +287,105 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn get_ref_revwalk_test() -> Result<(), Box<dyn error::Error>> {
+        let (dir, repo) = test_repo("mmcj_refs_test")?;
+
+        // one commit on HEAD, one only on a remote branch and one only on a tag
+        let first = repo.find_commit(commit_at(&repo, 1_576_800_000, "first", &[])?)?;
+        let (sig, tree) = (first.author(), first.tree()?);
+        let remote = repo.commit(None, &sig, &sig, "remote", &tree, &[&first])?;
+        repo.reference("refs/remotes/origin/main", remote, false, "")?;
+        repo.reference_symbolic("refs/remotes/origin/HEAD", "refs/remotes/origin/main", false, "")?;
+        let tagged = repo.commit(None, &sig, &sig, "tagged", &tree, &[&first])?;
+        let tagged = repo.find_object(tagged, None)?;
+        repo.tag("v1.0", &tagged, &sig, "release", false)?;
+        repo.tag_lightweight("v1.0-light", &tagged, false)?;
+
+        let count = |refs: &RefSelection| -> Result<usize, Box<dyn error::Error>> {
+            Ok(get_ref_revwalk(&repo, refs, &[])?.count())
+        };
+        assert_eq!(1, count(&RefSelection::default())?);
+        let all = RefSelection {
+            remote: true,
+            tags: true,
+            ..RefSelection::default()
+        };
+        assert_eq!(3, count(&all)?, "Commits reachable from many refs should only show up once");
+        let head = RefSelection {
+            head_only: true,
+            ..all.clone()
+        };
+        assert_eq!(1, count(&head)?);
+        let pattern = RefSelection {
+            local: false,
+            patterns: vec![String::from("refs/tags/*-light")],
+            ..RefSelection::default()
+        };
+        assert_eq!(2, count(&pattern)?);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn filter_by_window_test() -> Result<(), Box<dyn error::Error>> {
+        let (dir, repo) = test_repo("mmcj_window_test")?;
+
+        // a commit at noon each day from 2019-12-01 to 2019-12-10
+        for day in 1..=10 {
+            let time = chrono::NaiveDate::from_ymd(2019, 12, day).and_hms(12, 0, 0).timestamp();
+            commit_at(&repo, time, &day.to_string(), &[])?;
+        }
+
+        let day = chrono::NaiveDate::from_ymd(2019, 12, 5);
+        let window = TimeWindow::from_days(Some(day), Some(day));
+        let identities = Identities::new(&[String::from("celnardur@pm.com")], &[]);
+        let walk = get_repo_revwalk(&repo)?;
+        let commits = filter_by_identity(&repo, walk, &identities, &Mailmap::default(), &window)?;
+        // the window is a couple days wider than the day itself
+        let days: Vec<&str> = commits.iter().map(|c| c.message().unwrap_or("")).collect();
+        assert_eq!(vec!["7", "6", "5", "4", "3"], days);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn filter_by_identity_test() -> Result<(), Box<dyn error::Error>> {
+        let (dir, repo) = test_repo("mmcj_identity_test")?;
+        std::fs::write(dir.join(".mailmap"), "<celnardur@protonmail.com> <me@old-laptop.local>\n")?;
+
+        let mut head = None;
+        for (name, email, message) in &[
+            ("Celnardur", "me@old-laptop.local", "commit"),
+            ("Celnardur", "Celnardur@ProtonMail.com", "commit"),
+            ("Aaron", "aaron@ourcompany.com", "commit"),
+            ("Celnardur", "1234+celnardur@users.noreply.github.com", "commit"),
+            ("Someone", "someone@pm.com", "pair\n\nCo-authored-by: Me <me@old-laptop.local>"),
+        ] {
+            head = Some(commit_as(&repo, &git2::Signature::now(name, email)?, message, &[])?);
+        }
+        let head = head.unwrap();
+
+        let mut walk = repo.revwalk()?;
+        walk.push(head)?;
+        let commits = filter_by_email(&repo, walk, &[String::from("celnardur@protonmail.com")])?;
+        assert_eq!(3, commits.len(), "Mailmapped, co-authored and differently cased emails should match");
+        let info = CommitInfo::new(&repo, &commits[0]);
+        let identities = Identities::new(&[String::from("celnardur@protonmail.com")], &[]);
+        assert_eq!(vec!["Someone"], paired_with(&info, &identities, &Mailmap::from_repo(&repo)));
+
+        let mut walk = repo.revwalk()?;
+        walk.push(head)?;
+        let identities = Identities::new(&[String::from("*@OurCompany.com")], &[String::from("celnardur")]);
+        let commits = filter_by_identity(&repo, walk, &identities, &Mailmap::from_repo(&repo), &TimeWindow::all())?;
+        assert_eq!(4, commits.len(), "Globs and names should match every commit by us");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
 }
 
 // Error Class
@@ -1,56 +1,259 @@
-use git2::Repository;
+use chrono::Local;
 use mmcj::*;
-use std::{io, io::Write, process};
-use termion::{color, event::Key, input::TermRead, raw::IntoRawMode};
+use std::{env, fs, io, io::Write, path::Path, process};
+use termion::{clear, event::Key, input::TermRead, raw::IntoRawMode};
 
 fn main() {
     /*
     let config = Config::default();
     config.save("default_config.json");
     */
-    let config = match Config::new() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(a) => a,
+        Err(e) => {
+            println!("{}\n\n{}", e, cli::USAGE);
+            process::exit(1);
+        }
+    };
+    if args.command == Command::Help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+
+    let config = match &args.config {
+        Some(path) => Ok(path.clone()),
+        None => config::get_settings_path(),
+    }
+    .and_then(|path| load_settings(&path, &args.command));
+    let mut config = match config {
         Ok(c) => c,
         Err(e) => {
             println!("Config error: {}", e);
             process::exit(1);
         }
     };
+    if !args.repos.is_empty() {
+        config.repos = args.repos.iter().map(|r| RepoSettings::new(r)).collect();
+        config.discovery.roots.clear();
+    }
+    if !args.emails.is_empty() {
+        config.emails = args.emails.clone();
+    }
 
-    if let Err(e) = run(config) {
+    if let Err(e) = run(config, args) {
         println!("Application error: {}", e);
         process::exit(1);
     }
 }
 
-// Application Logic
+/// Reads the settings at path. The setup wizard makes them for the setup command, and
+/// on the first run of the TUI, but other commands can be scripted so they fail instead.
+fn load_settings(path: &str, command: &Command) -> Result<Config, Box<dyn std::error::Error>> {
+    let exists = Path::new(path).exists();
+    if *command == Command::Setup || (!exists && *command == Command::Tui) {
+        return setup::setup_settings(path);
+    }
+    if !exists {
+        let message = format!("There are no settings at {}, run mmcj setup to make them", path);
+        return Err(Box::new(mmcj::Error::new(&message)));
+    }
+    Config::from_path(path)
+}
 
-pub fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
-    let mut diffs: Vec<Box<dyn Expand>> = Vec::new();
+// Application Logic
 
-    for repo in &config.repos {
-        let repo = Repository::open(repo)?;
-        let walk = get_repo_revwalk(&repo)?;
-        let commits = filter_by_email(&repo, walk, &config.emails)?;
-        for commit in commits {
-            if let Ok(journal_diff) = JournalDiff::from_commit(&repo, &commit) {
-                diffs.push(Box::new(journal_diff));
+pub fn run(config: Config, args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    match args.command {
+        Command::Tui => run_tui(&config, &args),
+        Command::Log => run_log(&config, &args),
+        Command::Stats => run_stats(&config, &args),
+        Command::Export => run_export(&config, &args),
+        Command::Config => {
+            println!("{}", serde_json::to_string_pretty(&config)?);
+            Ok(())
+        }
+        Command::Entry => {
+            let today = timeline::journal_day_naive(&Local::now().naive_local(), config.day_change_time);
+            match entry::write_entry(entry::entry_time(today, config.day_change_time), Vec::new())? {
+                Some(written) => println!("Saved {}", written.heading()),
+                None => println!("Nothing was written so no entry was saved"),
             }
+            Ok(())
         }
+        Command::Tags => {
+            let entries = get_entries(&config, &args)?;
+            for (tag, count) in TagIndex::new(&entries, config.day_change_time).tags() {
+                println!("{:<24} {:>6} entries", format!("#{}", tag), count);
+            }
+            Ok(())
+        }
+        Command::Setup | Command::Help => Ok(()),
+    }
+}
+
+/// Diffs every commit by the configured emails and names that falls in the date range,
+/// oldest first. With --tag, only the commits the entries from get_entries tag are kept.
+fn get_diffs(config: &Config, args: &Args, entries: &[Entry]) -> Result<Vec<JournalDiff>, Box<dyn std::error::Error>> {
+    let options = scan::ScanOptions {
+        identities: config.identities(),
+        // skips walking commits that can't be in range, they still get checked exactly below
+        window: timeline::TimeWindow::from_days(args.since, args.until),
+        // without a cache everything still works, just slower
+        cache: if args.no_cache {
+            None
+        } else {
+            cache::get_cache_path().ok().and_then(|p| DiffCache::open(Path::new(&p)).ok())
+        },
+        // only the TUI can leave out the files of a commit until it is expanded
+        lazy: args.command == Command::Tui,
+    };
+
+    // progress goes to standard error so it doesn't end up in piped output
+    let show_progress = termion::is_tty(&io::stderr());
+    let mut diffs = scan::scan_repos(
+        &config.all_repos(),
+        &options,
+        config.scan_threads,
+        |progress| {
+            if show_progress {
+                eprint!(
+                    "\r{}Scanning {}/{} {}",
+                    clear::CurrentLine,
+                    progress.done,
+                    progress.total,
+                    progress.repo,
+                );
+            }
+        },
+    )?;
+    if show_progress {
+        eprint!("\r{}", clear::CurrentLine);
     }
 
-    let mut fl = FoldingList::new(diffs)?;
+    diffs.retain(|d| match d.commit() {
+        Some(c) => args.in_range(timeline::journal_day(&c.time(), config.day_change_time)),
+        None => false,
+    });
+    if !args.tags.is_empty() {
+        let index = TagIndex::new(entries, config.day_change_time);
+        diffs.retain(|d| d.commit().is_some_and(|c| index.has_commit(&args.tags, c)));
+    }
+    Ok(diffs)
+}
+
+/// The saved entries that fall in the date range and have one of the tags, oldest first
+fn get_entries(config: &Config, args: &Args) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let (mut entries, skipped) = Entry::load_all(Path::new(&entry::get_entries_path()?))?;
+    for message in skipped {
+        eprintln!("Skipped entry {}", message);
+    }
+    entries.retain(|e| args.in_range(timeline::journal_day_naive(&e.time, config.day_change_time)));
+    if !args.tags.is_empty() {
+        entries.retain(|e| e.has_any_tag(&args.tags));
+    }
+    Ok(entries)
+}
+
+fn journal_list(config: &Config, diffs: &[JournalDiff], entries: &[Entry]) -> Result<FoldingList, Box<dyn std::error::Error>> {
+    let mut years: Vec<Box<dyn Expand>> = Vec::new();
+    for year in timeline::group_by_year(diffs.to_vec(), entries.to_vec(), config.day_change_time) {
+        years.push(Box::new(year));
+    }
+
+    let mut fl = FoldingList::new(years)?;
+    fl.set_indent(&config.indent_string);
+    if let Ok((width, _)) = termion::terminal_size() {
+        fl.set_width(width);
+    }
+    Ok(fl)
+}
+
+fn run_tui(config: &Config, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = get_entries(config, args)?;
+    let diffs = get_diffs(config, args, &entries)?;
+
+    let mut fl = journal_list(config, &diffs, &entries)?;
     let stdin = io::stdin();
     let mut stdout = io::stdout().into_raw_mode()?;
     let cs = config.get_color_settings()?;
     write!(stdout,"{}", fl.render(&cs)?)?;
     stdout.flush()?;
 
-    for c in stdin.keys() {
+    let mut keys = stdin.keys();
+    while let Some(c) = keys.next() {
         match c? {
             Key::Char('j') => fl.scroll(1),
             Key::Char('k') => fl.scroll(-1),
             Key::Char('f') => fl.collapse(),
             Key::Char('d') => fl.expand(),
+            Key::Char('n') => {
+                // the editor needs the terminal back the way it normally is
+                let today = timeline::journal_day_naive(&Local::now().naive_local(), config.day_change_time);
+                let day = fl.selected_date().unwrap_or(today);
+                // writing about a commit links the entry to it
+                let references = diffs
+                    .iter()
+                    .filter_map(|d| d.commit())
+                    .find(|c| fl.selected_under(&c.target()))
+                    .map(entry::Reference::to_commit)
+                    .into_iter()
+                    .collect();
+                stdout.suspend_raw_mode()?;
+                let written = entry::write_entry(entry::entry_time(day, config.day_change_time), references);
+                stdout.activate_raw_mode()?;
+
+                if let Some(new) = written? {
+                    let target = new.target();
+                    entries.push(new);
+                    fl = journal_list(config, &diffs, &entries)?;
+                    fl.reveal(&target);
+                }
+            }
+            Key::Char('e') => {
+                let pos = match entries.iter().position(|e| fl.selected_under(&e.target())) {
+                    Some(p) => p,
+                    None => continue,
+                };
+                stdout.suspend_raw_mode()?;
+                let edited = entry::edit_entry(&mut entries[pos]);
+                stdout.activate_raw_mode()?;
+
+                if edited? {
+                    let target = entries[pos].target();
+                    fl = journal_list(config, &diffs, &entries)?;
+                    fl.reveal(&target);
+                }
+            }
+            Key::Char('x') => {
+                let pos = match entries.iter().position(|e| fl.selected_under(&e.target())) {
+                    Some(p) => p,
+                    None => continue,
+                };
+                let (_, height) = termion::terminal_size()?;
+                write!(
+                    stdout,
+                    "{}{}Delete \"{}\"? (y/n)",
+                    termion::cursor::Goto(1, height),
+                    clear::CurrentLine,
+                    entries[pos].heading(),
+                )?;
+                stdout.flush()?;
+
+                if let Some(Ok(Key::Char('y'))) = keys.next() {
+                    Entry::delete(Path::new(&entry::get_entries_path()?), &entries[pos].id)?;
+                    entries.remove(pos);
+                    // there's nothing left to show
+                    if diffs.is_empty() && entries.is_empty() {
+                        break;
+                    }
+                    fl = journal_list(config, &diffs, &entries)?;
+                }
+            }
+            Key::Char('g') => {
+                if let Some(target) = fl.selected().link() {
+                    fl.reveal(&target);
+                }
+            }
             Key::Char('q') => break,
             _ => continue,
         }
@@ -59,3 +262,95 @@ pub fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
     }
     Ok(())
 }
+
+fn run_log(config: &Config, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    for diff in get_diffs(config, args, &get_entries(config, args)?)? {
+        let commit = match diff.commit() {
+            Some(c) => c,
+            None => continue,
+        };
+        let counts = diff.counts().unwrap_or_else(LineCounts::new);
+        println!(
+            "{} {} {} +{} -{} ~{} {}{}",
+            commit.short_id(),
+            commit.time().format("%Y-%m-%d %H:%M"),
+            commit.repo,
+            counts.added(),
+            counts.deleted(),
+            counts.modified(),
+            commit.summary(),
+            commit.paired_label(),
+        );
+    }
+    Ok(())
+}
+
+fn run_stats(config: &Config, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    // (repo, commits, paired commits, line counts) in the order repos first show up
+    let mut stats: Vec<(String, usize, usize, LineCounts)> = Vec::new();
+    let mut total = LineCounts::new();
+    let mut commits = 0;
+    let mut paired = 0;
+    let entries = get_entries(config, args)?;
+    for diff in get_diffs(config, args, &entries)? {
+        let commit = match diff.commit() {
+            Some(c) => c,
+            None => continue,
+        };
+        // paired commits count by paired_weight since the lines weren't all ours
+        let is_paired = !commit.paired_with.is_empty();
+        let mut counts = diff.counts().unwrap_or_else(LineCounts::new);
+        if is_paired {
+            counts = counts.scaled(config.paired_weight);
+        }
+        let pos = match stats.iter().position(|s| s.0 == commit.repo) {
+            Some(p) => p,
+            None => {
+                stats.push((commit.repo.clone(), 0, 0, LineCounts::new()));
+                stats.len() - 1
+            }
+        };
+        stats[pos].1 += 1;
+        stats[pos].2 += is_paired as usize;
+        stats[pos].3.add(&counts);
+        total.add(&counts);
+        commits += 1;
+        paired += is_paired as usize;
+    }
+    stats.push((String::from("total"), commits, paired, total));
+
+    for (repo, commits, paired, counts) in stats {
+        println!(
+            "{:<24} {:>6} commits {:>6} paired {:>8} added {:>8} deleted {:>8} modified",
+            repo,
+            commits,
+            paired,
+            counts.added(),
+            counts.deleted(),
+            counts.modified(),
+        );
+    }
+
+    // the average ratings of every entry in the date range, or with the tags
+    let average = |ratings: Vec<u8>| match ratings.len() {
+        0 => String::from("-"),
+        n => format!("{:.1}", ratings.iter().map(|r| *r as f64).sum::<f64>() / n as f64),
+    };
+    println!(
+        "{:<24} {:>6} entries {:>6} mood {:>6} energy",
+        "journal",
+        entries.len(),
+        average(entries.iter().filter_map(|e| e.mood()).collect()),
+        average(entries.iter().filter_map(|e| e.energy()).collect()),
+    );
+    Ok(())
+}
+
+fn run_export(config: &Config, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let text = serde_json::to_string_pretty(&get_diffs(config, args, &get_entries(config, args)?)?)?;
+    match &args.output {
+        Some(path) => fs::write(path, text)?,
+        None => println!("{}", text),
+    }
+    Ok(())
+}
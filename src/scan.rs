@@ -0,0 +1,136 @@
+use crate::{cache::DiffCache, identity::Identities, scan_repo, timeline::TimeWindow, JournalDiff, RepoSettings};
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+/// What to look for in each repository and how to diff it
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub identities: Identities,
+    pub window: TimeWindow,
+    pub cache: Option<DiffCache>,
+    // only keep the line counts of each commit and load its files when it's expanded
+    pub lazy: bool,
+}
+
+impl ScanOptions {
+    /// Full diffs of every commit by the identities, without a cache
+    pub fn new(identities: Identities) -> ScanOptions {
+        ScanOptions {
+            identities,
+            window: TimeWindow::all(),
+            cache: None,
+            lazy: false,
+        }
+    }
+}
+
+/// How far a scan has got, given to the progress callback as each repository finishes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+    pub repo: String,
+}
+
+/// Scans the repositories on up to `workers` threads at once, or one per processor if
+/// it's 0, and returns every diff oldest first. Progress is reported on the calling
+/// thread. If any repositories fail, the first one in the list is the error.
+pub fn scan_repos<F: FnMut(&Progress)>(
+    repos: &[RepoSettings],
+    options: &ScanOptions,
+    workers: usize,
+    mut progress: F,
+) -> Result<Vec<JournalDiff>, Box<dyn Error>> {
+    let workers = match workers {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
+    };
+    let workers = workers.min(repos.len()).max(1);
+
+    // errors are turned into strings since boxed errors can't be sent between threads
+    let mut results: Vec<Option<Result<Vec<JournalDiff>, String>>> = repos.iter().map(|_| None).collect();
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let next = &next;
+            // each worker takes the next repository nobody has started yet
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let settings = match repos.get(index) {
+                    Some(s) => s,
+                    None => break,
+                };
+                let result = scan_repo(settings, options).map_err(|e| e.to_string());
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (done, (index, result)) in receiver.iter().enumerate() {
+            progress(&Progress {
+                done: done + 1,
+                total: repos.len(),
+                repo: repos[index].name(),
+            });
+            results[index] = Some(result);
+        }
+    });
+
+    let mut diffs = Vec::new();
+    for (settings, result) in repos.iter().zip(results) {
+        match result {
+            Some(Ok(found)) => diffs.extend(found),
+            Some(Err(e)) => return Err(Box::new(crate::Error::new(&format!("{}: {}", settings.path, e)))),
+            None => (),
+        }
+    }
+    diffs.sort_by_key(|d| d.commit().map(|c| c.time()));
+    Ok(diffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{commit_at, test_repo};
+
+    #[test]
+    fn scan_repos_test() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join("mmcj_scan_test");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // three repositories with their commits interleaved in time
+        let mut repos = Vec::new();
+        for (num, name) in ["one", "two", "three"].iter().enumerate() {
+            let (path, repo) = test_repo(&format!("mmcj_scan_test/{}", name))?;
+            for hour in 0..3 {
+                commit_at(&repo, 1_576_800_000 + (hour * 3 + num as i64) * 3600, name, &[])?;
+            }
+            repos.push(RepoSettings::new(&path.to_string_lossy()));
+        }
+
+        let options = ScanOptions::new(Identities::new(&[String::from("celnardur@pm.com")], &[]));
+        let mut reports = Vec::new();
+        let diffs = scan_repos(&repos, &options, 2, |p| reports.push(p.clone()))?;
+        let names: Vec<&str> = diffs.iter().filter_map(|d| d.commit()).map(|c| c.summary()).collect();
+        assert_eq!(vec!["one", "two", "three", "one", "two", "three", "one", "two", "three"], names);
+        assert_eq!(vec![1, 2, 3], reports.iter().map(|p| p.done).collect::<Vec<usize>>());
+        assert!(reports.iter().all(|p| p.total == 3));
+
+        repos.insert(1, RepoSettings::new(&dir.join("missing").to_string_lossy()));
+        let result = scan_repos(&repos, &options, 0, |_| ());
+        assert!(result.unwrap_err().to_string().contains("missing"));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
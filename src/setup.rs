@@ -0,0 +1,216 @@
+use crate::{
+    config::{get_color_escape, RepoSettings},
+    discovery::{expand_home, Discovery},
+    Config,
+};
+use std::{
+    error::Error,
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+use termion::color;
+
+// how many directories deep to look for repositories
+const SEARCH_DEPTH: usize = 3;
+
+/// The name and email from the global git config
+#[derive(Debug, Clone)]
+pub struct GitIdentity {
+    pub name: String,
+    pub email: String,
+}
+
+pub fn git_identity() -> Option<GitIdentity> {
+    let config = git2::Config::open_default().ok()?;
+    Some(GitIdentity {
+        name: config.get_string("user.name").unwrap_or_default(),
+        email: config.get_string("user.email").ok()?,
+    })
+}
+
+/// Walks the user through making settings on standard in and out, then saves them
+/// to path.
+pub fn setup_settings(path: &str) -> Result<Config, Box<dyn Error>> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let config = run(&mut stdin.lock(), &mut stdout.lock(), git_identity())?;
+
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    config.save(path)?;
+    println!("Saved settings to {}", path);
+    Ok(config)
+}
+
+/// Asks for emails, repositories and colors, starting from the default settings
+pub fn run<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    identity: Option<GitIdentity>,
+) -> Result<Config, Box<dyn Error>> {
+    let mut config = Config::default();
+    writeln!(output, "Let's set up mmcj. Press enter to take the default in [brackets].\n")?;
+
+    // Emails
+    if let Some(id) = identity {
+        writeln!(output, "Found git identity {} <{}>", id.name, id.email)?;
+        if yes(&prompt(input, output, "Include commits by this email? [Y/n] ", "y")?) {
+            config.emails.push(id.email);
+        }
+    }
+    let extra = prompt(input, output, "Other emails you commit with, separated by spaces: ", "")?;
+    config.emails.extend(extra.split_whitespace().map(String::from));
+
+    // Repositories
+    let home = std::env::var("HOME").unwrap_or_else(|_| String::from("."));
+    let question = format!("Directory to search for repositories (- to skip) [{}]: ", home);
+    let dir = prompt(input, output, &question, &home)?;
+    if dir != "-" {
+        let found = find_repos(&expand_home(&dir));
+        if found.is_empty() {
+            writeln!(output, "No repositories found in {}", dir)?;
+        } else {
+            for (num, repo) in found.iter().enumerate() {
+                writeln!(output, "{:>4}  {}", num + 1, repo.display())?;
+            }
+            let picks = prompt(input, output, "Repositories to include by number, or none [all]: ", "all")?;
+            for num in parse_picks(&picks, found.len()) {
+                config.repos.push(RepoSettings::new(&found[num].to_string_lossy()));
+            }
+        }
+    }
+    let extra = prompt(input, output, "Other repository paths, separated by spaces: ", "")?;
+    config.repos.extend(extra.split_whitespace().map(RepoSettings::new));
+
+    // Colors
+    writeln!(
+        output,
+        "\nColors can be a name like \"light blue\" or three numbers for red, green and blue."
+    )?;
+    for (setting, sample) in &[("add", "+ added line"), ("delete", "- deleted line"), ("modify", "~ modified line")] {
+        loop {
+            let current = config.foreground_colors[*setting].clone();
+            writeln!(
+                output,
+                "{}{}{}",
+                get_color_escape(&current, true)?,
+                sample,
+                color::Reset.fg_str(),
+            )?;
+            let answer = prompt(input, output, &format!("{} color [{}]: ", setting, current), &current)?;
+            match get_color_escape(&answer, true) {
+                Ok(_) if answer == current => break,
+                Ok(_) => {
+                    config.foreground_colors.insert(setting.to_string(), answer);
+                }
+                Err(_) => writeln!(output, "{} isn't a color", answer)?,
+            }
+        }
+    }
+
+    writeln!(output)?;
+    Ok(config)
+}
+
+/// Finds git repositories in dir and its subdirectories, including repositories nested
+/// inside other repositories but not hidden directories.
+pub fn find_repos(dir: &Path) -> Vec<PathBuf> {
+    let search = Discovery {
+        max_depth: SEARCH_DEPTH,
+        ..Discovery::default()
+    };
+    search.find(dir)
+}
+
+fn prompt<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: &str,
+) -> Result<String, Box<dyn Error>> {
+    write!(output, "{}", question)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        Ok(default.to_string())
+    } else {
+        Ok(answer.to_string())
+    }
+}
+
+fn yes(answer: &str) -> bool {
+    answer.to_lowercase().starts_with('y')
+}
+
+// turns "all", "none" or "1 3 4" into indexes of the listed items
+fn parse_picks(picks: &str, len: usize) -> Vec<usize> {
+    match picks {
+        "all" => (0..len).collect(),
+        "none" => Vec::new(),
+        _ => picks
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter_map(|p| p.parse::<usize>().ok())
+            .filter(|&n| n >= 1 && n <= len)
+            .map(|n| n - 1)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Repository;
+
+    #[test]
+    fn parse_picks_test() {
+        assert_eq!(vec![0, 1, 2], parse_picks("all", 3));
+        assert!(parse_picks("none", 3).is_empty());
+        assert_eq!(vec![0, 2], parse_picks("1, 3 7", 3));
+    }
+
+    #[test]
+    fn run_test() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join("mmcj_setup_test");
+        let _ = fs::remove_dir_all(&dir);
+        Repository::init(dir.join("code/one"))?;
+        Repository::init(dir.join("code/two"))?;
+        Repository::init(dir.join("code/two/lib"))?;
+        Repository::init(dir.join(".hidden/three"))?;
+
+        let found = find_repos(&dir);
+        assert_eq!(
+            vec![dir.join("code/one"), dir.join("code/two"), dir.join("code/two/lib")],
+            found
+        );
+
+        let identity = GitIdentity {
+            name: String::from("Celnardur"),
+            email: String::from("celnardur@protonmail.com"),
+        };
+        let answers = format!(
+            "\ncelnardur@pm.com\n{}\n2\nmmcj_test_repo\n\nnot a color\n10 20 30\n10 20 30\n\n",
+            dir.display()
+        );
+        let mut output = Vec::new();
+        let config = run(&mut answers.as_bytes(), &mut output, Some(identity))?;
+
+        assert_eq!(vec!["celnardur@protonmail.com", "celnardur@pm.com"], config.emails);
+        assert_eq!(
+            vec![
+                RepoSettings::new(&dir.join("code/two").to_string_lossy()),
+                RepoSettings::new("mmcj_test_repo"),
+            ],
+            config.repos
+        );
+        assert_eq!("green", config.foreground_colors["add"]);
+        assert_eq!("10 20 30", config.foreground_colors["delete"]);
+        assert!(String::from_utf8(output)?.contains("not a color isn't a color"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{timeline, CommitInfo, Entry};
+use chrono::NaiveDate;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+/// The tags written anywhere in text like #debugging, lowercased and in the order
+/// they first show up. Markdown headings, numbers like #12, the # in the middle of a
+/// word or link and anything in a fenced code block aren't tags.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        for (i, ch) in chars.iter().enumerate() {
+            // links like https://example.com/#section have a / or : before the #
+            if *ch != '#' || (i > 0 && (chars[i - 1].is_alphanumeric() || "#/:".contains(chars[i - 1]))) {
+                continue;
+            }
+            let tag: String = chars[i + 1..].iter().take_while(|c| is_tag_char(**c)).collect();
+            // so #debugging/ at the end of a sentence doesn't keep the slash
+            let tag = tag.trim_end_matches(['-', '/']).to_lowercase();
+            if tag.starts_with(char::is_alphabetic) && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || c == '/'
+}
+
+/// The `key: value` lines in the last paragraph of text, like git trailers. Keys are
+/// lowercased. If any line in the last paragraph isn't one, there are no fields.
+pub fn parse_fields(text: &str) -> Vec<(String, String)> {
+    let mut paragraph = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            paragraph.clear();
+        } else {
+            paragraph.push(line);
+        }
+    }
+
+    let mut fields = Vec::new();
+    for line in paragraph {
+        match line.split_once(':') {
+            Some((key, value)) if is_key(key.trim()) && !value.trim().is_empty() => {
+                fields.push((key.trim().to_lowercase(), value.trim().to_string()));
+            }
+            _ => return Vec::new(),
+        }
+    }
+    fields
+}
+
+fn is_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A rating from 1 to 5, written like 4 or 4/5
+pub fn parse_rating(value: &str) -> Option<u8> {
+    let rating = match value.split_once('/') {
+        Some((rating, "5")) => rating,
+        Some(_) => return None,
+        None => value,
+    };
+    rating.trim().parse().ok().filter(|r| (1..=5).contains(r))
+}
+
+/// Which journal days and commits each tag is on, from the entries that have it. A
+/// commit has a tag when an entry with the tag references it or was written on the
+/// same journal day.
+#[derive(Debug, Clone, Default)]
+pub struct TagIndex {
+    // tag to number of entries
+    entries: BTreeMap<String, usize>,
+    days: HashMap<String, HashSet<NaiveDate>>,
+    // the repository path and commit id of each reference
+    commits: HashMap<String, HashSet<(String, String)>>,
+    day_change_time: i32,
+}
+
+impl TagIndex {
+    pub fn new(entries: &[Entry], day_change_time: i32) -> TagIndex {
+        let mut index = TagIndex {
+            day_change_time,
+            ..TagIndex::default()
+        };
+        for entry in entries {
+            let day = timeline::journal_day_naive(&entry.time, day_change_time);
+            for tag in entry.tags() {
+                *index.entries.entry(tag.clone()).or_insert(0) += 1;
+                index.days.entry(tag.clone()).or_default().insert(day);
+                let commits = index.commits.entry(tag).or_default();
+                commits.extend(entry.references.iter().map(|r| (r.repo_path.clone(), r.commit.clone())));
+            }
+        }
+        index
+    }
+
+    /// Every tag with how many entries have it, by name
+    pub fn tags(&self) -> Vec<(&str, usize)> {
+        self.entries.iter().map(|(tag, count)| (tag.as_str(), *count)).collect()
+    }
+
+    /// Whether the commit has any of the tags
+    pub fn has_commit(&self, tags: &[String], commit: &CommitInfo) -> bool {
+        let day = timeline::journal_day(&commit.time(), self.day_change_time);
+        // references can have short ids, and paths written with or without a trailing /
+        let referenced = |(repo_path, id): &(String, String)| {
+            commit.id.starts_with(id.as_str()) && Path::new(repo_path) == Path::new(&commit.repo_path)
+        };
+        tags.iter().any(|tag| {
+            self.days.get(tag).is_some_and(|days| days.contains(&day))
+                || self.commits.get(tag).is_some_and(|refs| refs.iter().any(referenced))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::Reference;
+
+    #[test]
+    fn parse_tags_test() {
+        let text = "# Fixed it #Debugging\n\n\
+                    Took all day (#folding-list, #debugging). See issue #12 and page#anchor.\n\
+                    ## Heading\n#rust/async/ is next";
+        assert_eq!(vec!["debugging", "folding-list", "rust/async"], parse_tags(text));
+        assert!(parse_tags("# Just a heading\n&#39;").is_empty());
+        assert!(parse_tags("See https://example.com/#section and C:#drive").is_empty());
+        let text = "Fixed the build #ci\n```c\n#include <stdio.h>\n#define MAX 10\n```\n~~~\n#ifdef\n~~~\n#after";
+        assert_eq!(vec!["ci", "after"], parse_tags(text));
+    }
+
+    #[test]
+    fn parse_fields_test() {
+        let text = "Note: this isn't a field\n\nmood: 4\nEnergy: 2/5\nfocus-time: 3 hours";
+        assert_eq!(
+            vec![
+                (String::from("mood"), String::from("4")),
+                (String::from("energy"), String::from("2/5")),
+                (String::from("focus-time"), String::from("3 hours")),
+            ],
+            parse_fields(text)
+        );
+        assert!(parse_fields("mood: 4\nand then some prose").is_empty());
+        assert!(parse_fields("a sentence: with spaces in the key").is_empty());
+
+        assert_eq!(Some(4), parse_rating("4"));
+        assert_eq!(Some(2), parse_rating("2/5"));
+        assert_eq!(None, parse_rating("8/10"));
+        assert_eq!(None, parse_rating("0"));
+        assert_eq!(None, parse_rating("good"));
+    }
+
+    #[test]
+    fn tag_index_test() -> Result<(), Box<dyn std::error::Error>> {
+        let mut debugging = Entry::new(NaiveDate::from_ymd(2019, 12, 20).and_hms(14, 3, 0));
+        debugging.set_written("# Long day #debugging\n\nmood: 2\nenergy: 4/5");
+        debugging.references.push(Reference::parse("/code/mmcj | abcdef0")?);
+        let mut rust = Entry::new(NaiveDate::from_ymd(2019, 12, 21).and_hms(9, 0, 0));
+        rust.set_written("\nMore #Rust and #debugging");
+        assert_eq!(Some(2), debugging.mood());
+        assert_eq!(Some(4), debugging.energy());
+        assert_eq!(None, rust.mood());
+        assert!(rust.has_any_tag(&[String::from("rust")]));
+
+        let index = TagIndex::new(&[debugging, rust], 500);
+        assert_eq!(vec![("debugging", 2), ("rust", 1)], index.tags());
+
+        let commit = |id: &str, time: &str| -> Result<CommitInfo, Box<dyn std::error::Error>> {
+            Ok(serde_json::from_value(serde_json::json!({
+                "id": id, "repo": "mmcj", "repo_path": "/code/mmcj/", "message": "", "parents": [],
+                "author": {"name": "", "email": "", "time": time},
+                "committer": {"name": "", "email": "", "time": time},
+            }))?)
+        };
+        let rust_tag = [String::from("rust")];
+        let debugging_tag = [String::from("debugging")];
+        // the same day as the rust entry
+        assert!(index.has_commit(&rust_tag, &commit("1111111", "2019-12-21T16:00:00-07:00")?));
+        // referenced by the debugging entry, but on another day
+        assert!(index.has_commit(&debugging_tag, &commit("abcdef0123", "2019-11-02T10:00:00-07:00")?));
+        assert!(!index.has_commit(&rust_tag, &commit("abcdef0123", "2019-11-02T10:00:00-07:00")?));
+        // the same id in another repository isn't the referenced commit
+        let mut other = commit("abcdef0123", "2019-11-02T10:00:00-07:00")?;
+        other.repo_path = String::from("/code/other/");
+        assert!(!index.has_commit(&debugging_tag, &other));
+        Ok(())
+    }
+}
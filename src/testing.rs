@@ -0,0 +1,46 @@
+use git2::{Commit, Oid, Repository, Signature, Time};
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A new repository in the temp directory, replacing anything an earlier run left there
+pub fn test_repo(name: &str) -> Result<(PathBuf, Repository), Box<dyn Error>> {
+    let dir = env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    let repo = Repository::init(&dir)?;
+    Ok((dir, repo))
+}
+
+/// Writes the files, as paths and their text, and commits them on HEAD as Celnardur
+/// at time in seconds
+pub fn commit_at(repo: &Repository, time: i64, message: &str, files: &[(&str, &str)]) -> Result<Oid, Box<dyn Error>> {
+    let sig = Signature::new("Celnardur", "celnardur@pm.com", &Time::new(time, 0))?;
+    commit_as(repo, &sig, message, files)
+}
+
+/// Like commit_at, with someone else's name, email and time
+pub fn commit_as(
+    repo: &Repository,
+    sig: &Signature,
+    message: &str,
+    files: &[(&str, &str)],
+) -> Result<Oid, Box<dyn Error>> {
+    let dir = match repo.workdir() {
+        Some(d) => d,
+        None => return Err(Box::new(crate::Error::new("Test repository has no working directory"))),
+    };
+    let mut index = repo.index()?;
+    for (path, text) in files {
+        fs::write(dir.join(path), text)?;
+        index.add_path(Path::new(path))?;
+    }
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    // a new repository has no HEAD commit to follow on from
+    let parent = repo.head().and_then(|head| head.peel_to_commit()).ok();
+    let parents: Vec<&Commit> = parent.iter().collect();
+    Ok(repo.commit(Some("HEAD"), sig, sig, message, &tree, &parents)?)
+}
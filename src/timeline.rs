@@ -0,0 +1,354 @@
+use crate::{diffs::write_row, Colors, Entry, Expand, JournalDiff, LineCounts};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime};
+use std::{collections::BTreeMap, error::Error};
+
+/// The day something belongs to in the journal. Anything before the day change time
+/// (500 is 5:00am) counts as part of the previous day, so late nights stay together.
+pub fn journal_day(time: &DateTime<FixedOffset>, day_change_time: i32) -> NaiveDate {
+    journal_day_naive(&time.naive_local(), day_change_time)
+}
+
+/// The journal day of a local time without a timezone, like an entry's
+pub fn journal_day_naive(time: &NaiveDateTime, day_change_time: i32) -> NaiveDate {
+    let minutes = (day_change_time / 100) * 60 + day_change_time % 100;
+    (*time - Duration::minutes(minutes as i64)).date()
+}
+
+// days added on both sides of a time window to cover timezones, the day change time
+// and commits made on a computer with its clock a little off
+const WINDOW_SLACK_DAYS: i64 = 2;
+
+/// A rough range of commit times, in seconds since the epoch, for skipping commits
+/// while walking. It's wider than the journal days it's made from, so the days still
+/// have to be checked with journal_day afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TimeWindow {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl TimeWindow {
+    /// A window with no limits
+    pub fn all() -> TimeWindow {
+        TimeWindow::default()
+    }
+
+    pub fn from_days(since: Option<NaiveDate>, until: Option<NaiveDate>) -> TimeWindow {
+        TimeWindow {
+            since: since.map(|d| (d - Duration::days(WINDOW_SLACK_DAYS)).and_hms(0, 0, 0).timestamp()),
+            until: until.map(|d| (d + Duration::days(WINDOW_SLACK_DAYS + 1)).and_hms(0, 0, 0).timestamp()),
+        }
+    }
+
+    pub fn contains(&self, time: i64) -> bool {
+        !self.starts_after(time) && self.until.is_none_or(|until| time < until)
+    }
+
+    /// Whether the window only starts after the time
+    pub fn starts_after(&self, time: i64) -> bool {
+        self.since.is_some_and(|since| time < since)
+    }
+}
+
+/// Sorts diffs and entries into years, months and days, oldest first. Diffs without
+/// commit information can't be dated so they are left out.
+pub fn group_by_year(diffs: Vec<JournalDiff>, entries: Vec<Entry>, day_change_time: i32) -> Vec<Year> {
+    let mut days: BTreeMap<NaiveDate, (Vec<JournalDiff>, Vec<Entry>)> = BTreeMap::new();
+    for diff in diffs {
+        let date = match diff.commit() {
+            Some(c) => journal_day(&c.time(), day_change_time),
+            None => continue,
+        };
+        days.entry(date).or_default().0.push(diff);
+    }
+    for entry in entries {
+        let date = journal_day_naive(&entry.time, day_change_time);
+        days.entry(date).or_default().1.push(entry);
+    }
+
+    let mut years: Vec<Year> = Vec::new();
+    for (date, (mut diffs, mut entries)) in days {
+        diffs.sort_by_key(|d| d.commit().map(|c| c.time()));
+        entries.sort_by_key(|e| e.time);
+        let day = Day::new(date, diffs, entries);
+
+        if years.last().map(|y| y.year) != Some(date.year()) {
+            years.push(Year::new(date.year()));
+        }
+        let year = years.last_mut().unwrap();
+        if year.months.last().map(|m| m.month) != Some(date.month()) {
+            year.months.push(Month::new(date.year(), date.month()));
+        }
+        let month = year.months.last_mut().unwrap();
+
+        month.counts.add(&day.counts);
+        year.counts.add(&day.counts);
+        month.days.push(day);
+    }
+    years
+}
+
+#[derive(Debug, Clone)]
+pub struct Year {
+    year: i32,
+    counts: LineCounts,
+    months: Vec<Month>,
+}
+
+impl Year {
+    fn new(year: i32) -> Year {
+        Year {
+            year,
+            counts: LineCounts::new(),
+            months: Vec::new(),
+        }
+    }
+
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        write_row(stream, colors, width, &[("default", &self.year.to_string())])
+    }
+}
+
+impl Expand for Year {
+    fn expand(&self) -> (Vec<Box<dyn Expand>>, bool) {
+        let mut folds: Vec<Box<dyn Expand>> = Vec::new();
+        for month in &self.months {
+            folds.push(Box::new(month.clone()));
+        }
+        (folds, false)
+    }
+
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
+    fn counts(&self) -> Option<LineCounts> {
+        Some(self.counts.clone())
+    }
+
+    fn holds(&self, target: &str) -> bool {
+        self.months.iter().any(|m| m.holds(target))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Month {
+    year: i32,
+    month: u32,
+    counts: LineCounts,
+    days: Vec<Day>,
+}
+
+impl Month {
+    fn new(year: i32, month: u32) -> Month {
+        Month {
+            year,
+            month,
+            counts: LineCounts::new(),
+            days: Vec::new(),
+        }
+    }
+
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        let name = NaiveDate::from_ymd(self.year, self.month, 1).format("%B %Y").to_string();
+        write_row(stream, colors, width, &[("default", &name)])
+    }
+}
+
+impl Expand for Month {
+    fn expand(&self) -> (Vec<Box<dyn Expand>>, bool) {
+        let mut folds: Vec<Box<dyn Expand>> = Vec::new();
+        for day in &self.days {
+            folds.push(Box::new(day.clone()));
+        }
+        (folds, false)
+    }
+
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
+    fn counts(&self) -> Option<LineCounts> {
+        Some(self.counts.clone())
+    }
+
+    fn holds(&self, target: &str) -> bool {
+        self.days.iter().any(|d| d.holds(target))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Day {
+    date: NaiveDate,
+    counts: LineCounts,
+    diffs: Vec<JournalDiff>,
+    entries: Vec<Entry>,
+}
+
+impl Day {
+    fn new(date: NaiveDate, diffs: Vec<JournalDiff>, entries: Vec<Entry>) -> Day {
+        let mut counts = LineCounts::new();
+        for diff in &diffs {
+            if let Some(c) = diff.counts() {
+                counts.add(&c);
+            }
+        }
+        Day {
+            date,
+            counts,
+            diffs,
+            entries,
+        }
+    }
+
+    fn render(&self, stream: &mut String, colors: Option<&Colors>, width: u16) -> Result<(), Box<dyn Error>> {
+        let name = self.date.format("%A, %B %-d").to_string();
+        write_row(stream, colors, width, &[("default", &name)])
+    }
+}
+
+impl Expand for Day {
+    /// Commits and entries, merged by the local time they were made
+    fn expand(&self) -> (Vec<Box<dyn Expand>>, bool) {
+        let mut timed: Vec<(Option<NaiveDateTime>, Box<dyn Expand>)> = Vec::new();
+        for diff in &self.diffs {
+            timed.push((diff.commit().map(|c| c.time().naive_local()), Box::new(diff.clone())));
+        }
+        for entry in &self.entries {
+            timed.push((Some(entry.time), Box::new(entry.clone())));
+        }
+        // stable, so commits stay before entries made at the same time
+        timed.sort_by_key(|t| t.0);
+        (timed.into_iter().map(|t| t.1).collect(), false)
+    }
+
+    fn display(&self, stream: &mut String, colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, Some(colors), width)
+    }
+
+    fn highlight(&self, stream: &mut String, _colors: &Colors, _line: u16, width: u16) -> Result<(), Box<dyn Error>> {
+        self.render(stream, None, width)
+    }
+
+    fn counts(&self) -> Option<LineCounts> {
+        Some(self.counts.clone())
+    }
+
+    fn date(&self) -> Option<NaiveDate> {
+        Some(self.date)
+    }
+
+    fn holds(&self, target: &str) -> bool {
+        self.diffs.iter().any(|d| d.is(target)) || self.entries.iter().any(|e| e.is(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn test_diff(time: &str, added: usize) -> JournalDiff {
+        let signature = serde_json::json!({
+            "name": "Celnardur",
+            "email": "celnardur@pm.com",
+            "time": time,
+        });
+        serde_json::from_value(serde_json::json!({
+            "commit": {
+                "id": "aeb35fe55b5deabc36399617c9a7c9281226b67e",
+                "repo": "mmcj",
+                "repo_path": "mmcj",
+                "author": signature,
+                "committer": signature,
+                "message": "",
+                "parents": [],
+            },
+            "counts": { "added": added, "deleted": 0, "modified": 0 },
+            "files": [],
+            "parents": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn journal_day_test() {
+        let late = FixedOffset::east(0).ymd(2019, 12, 21).and_hms(4, 59, 0);
+        assert_eq!(NaiveDate::from_ymd(2019, 12, 20), journal_day(&late, 500));
+        let early = FixedOffset::east(0).ymd(2019, 12, 21).and_hms(5, 0, 0);
+        assert_eq!(NaiveDate::from_ymd(2019, 12, 21), journal_day(&early, 500));
+        assert_eq!(NaiveDate::from_ymd(2019, 12, 21), journal_day(&late, 0));
+        let half = FixedOffset::east(0).ymd(2019, 12, 21).and_hms(0, 20, 0);
+        assert_eq!(NaiveDate::from_ymd(2019, 12, 20), journal_day(&half, 30));
+    }
+
+    #[test]
+    fn time_window_test() {
+        let window = TimeWindow::from_days(Some(NaiveDate::from_ymd(2019, 12, 10)), Some(NaiveDate::from_ymd(2019, 12, 10)));
+        let time = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().timestamp();
+        assert!(window.contains(time("2019-12-10T12:00:00+00:00")));
+        // still the 10th in the journal since it's before the day change time
+        assert!(window.contains(time("2019-12-11T04:00:00-12:00")));
+        assert!(window.contains(time("2019-12-10T00:30:00+14:00")));
+        assert!(!window.contains(time("2019-12-20T12:00:00+00:00")));
+        assert!(window.starts_after(time("2019-12-01T12:00:00+00:00")));
+        assert!(!window.starts_after(time("2019-12-20T12:00:00+00:00")));
+        assert!(TimeWindow::all().contains(0));
+    }
+
+    #[test]
+    fn group_by_year_test() {
+        let diffs = vec![
+            test_diff("2020-01-01T03:00:00-05:00", 1),
+            test_diff("2019-12-31T22:00:00-05:00", 2),
+            test_diff("2019-11-02T12:00:00+01:00", 4),
+            test_diff("2020-01-01T09:00:00-05:00", 8),
+        ];
+        let years = group_by_year(diffs, Vec::new(), 500);
+
+        assert_eq!(2, years.len());
+        assert_eq!(2019, years[0].year);
+        assert_eq!(7, years[0].counts.added());
+        assert_eq!(vec![11, 12], years[0].months.iter().map(|m| m.month).collect::<Vec<_>>());
+
+        // the 3am commit on new years belongs to new years eve
+        let december = &years[0].months[1];
+        assert_eq!(1, december.days.len());
+        assert_eq!(2, december.days[0].diffs.len());
+        assert_eq!(3, december.days[0].counts.added());
+
+        assert_eq!(8, years[1].counts.added());
+        assert_eq!(NaiveDate::from_ymd(2020, 1, 1), years[1].months[0].days[0].date);
+    }
+
+    #[test]
+    fn day_entries_test() {
+        let diffs = vec![
+            test_diff("2020-01-01T03:00:00-05:00", 1),
+            test_diff("2019-12-31T22:00:00-05:00", 2),
+        ];
+        let mut entry = Entry::new(NaiveDate::from_ymd(2019, 12, 31).and_hms(23, 0, 0));
+        entry.set_written("New years eve");
+        let target = entry.target();
+        let years = group_by_year(diffs, vec![entry], 500);
+
+        let day = &years[0].months[0].days[0];
+        assert_eq!(Some(NaiveDate::from_ymd(2019, 12, 31)), day.date());
+        assert_eq!(3, day.counts.added(), "Entries shouldn't change the line counts");
+        assert!(years[0].holds(&target));
+        assert!(years[0].holds("commit aeb35fe55b5deabc36399617c9a7c9281226b67e"));
+        assert!(!years[0].holds("entry nothing"));
+
+        let (folds, _) = day.expand();
+        let is_entry: Vec<bool> = folds.iter().map(|f| f.is(&target)).collect();
+        assert_eq!(vec![false, true, false], is_entry);
+    }
+}